@@ -0,0 +1,85 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use cube::entities::*;
+use cube::read;
+
+fn scrambled() -> Cube {
+    read::read("Scramble { R U F' L D B2 }").0
+}
+
+/// The E-perm as a compound operation (16 moves)
+fn e_perm() -> Operation {
+    let ops = read::parse_ops("R B' R' F R B R' F' R B R' F R B' R' F'")
+        .unwrap()
+        .1;
+    Operation::Compound("E".to_string(), true, ops.data)
+}
+
+fn bench_move(c: &mut Criterion) {
+    let cube = scrambled();
+    let op = Operation::Right(true);
+    let mut group = c.benchmark_group("move");
+    group.bench_function("cycles", |b| {
+        b.iter(|| {
+            let mut cube = cube.clone();
+            cube.apply_by_cycles(black_box(&op));
+            cube
+        })
+    });
+    group.bench_function("permutation", |b| {
+        b.iter(|| {
+            let mut cube = cube.clone();
+            cube.apply(black_box(&op));
+            cube
+        })
+    });
+    let packed = PackedCube::pack(&cube);
+    let perm = Permutation::of(&op);
+    group.bench_function("packed", |b| b.iter(|| black_box(&packed).permute(&perm)));
+    group.finish();
+}
+
+fn bench_compound(c: &mut Criterion) {
+    let cube = scrambled();
+    let op = e_perm();
+    let mut group = c.benchmark_group("compound");
+    group.bench_function("cycles", |b| {
+        b.iter(|| {
+            let mut cube = cube.clone();
+            cube.apply_by_cycles(black_box(&op));
+            cube
+        })
+    });
+    group.bench_function("permutation", |b| {
+        b.iter(|| {
+            let mut cube = cube.clone();
+            cube.apply(black_box(&op));
+            cube
+        })
+    });
+    let packed = PackedCube::pack(&cube);
+    let perm = Permutation::of(&op);
+    group.bench_function("packed", |b| b.iter(|| black_box(&packed).permute(&perm)));
+    group.finish();
+}
+
+fn bench_scramble(c: &mut Criterion) {
+    let cube = scrambled();
+    let ops = read::parse_ops("D' R2 U F2 D' B2 L2 D2 R2 B2 U' F L' B' R' F2 U R F D R'")
+        .unwrap()
+        .1;
+    let mut group = c.benchmark_group("scramble");
+    group.bench_function("cycles", |b| {
+        b.iter(|| {
+            let mut cube = cube.clone();
+            for op in black_box(&ops).data.iter() {
+                cube.apply_by_cycles(op);
+            }
+            cube
+        })
+    });
+    group.bench_function("permutation", |b| b.iter(|| black_box(&ops).apply(&cube)));
+    group.finish();
+}
+
+criterion_group!(benches, bench_move, bench_compound, bench_scramble);
+criterion_main!(benches);
@@ -0,0 +1,28 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use cube::read;
+use cube::solver::{search_with, SearchOptions};
+
+/// A search to the solved cube, which has all the symmetries
+fn bench_symmetry(c: &mut Criterion) {
+    let (cube, goal) = read::read("Scramble { F R' D' R U2 R' D R }");
+    let allowed_ops = read::parse_ops("U U' D D' F F' B B' L L' R R'")
+        .unwrap()
+        .1
+        .data;
+    let mut group = c.benchmark_group("symmetry");
+    group.sample_size(10);
+    for symmetry in [false, true] {
+        let options = SearchOptions {
+            symmetry,
+            ..SearchOptions::new(11, 1)
+        };
+        let name = if symmetry { "reduced" } else { "plain" };
+        group.bench_function(name, |b| {
+            b.iter(|| search_with(&cube, &goal, allowed_ops.clone(), &options))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_symmetry);
+criterion_main!(benches);
@@ -10,6 +10,19 @@ pub enum Color {
     Wildcard,
 }
 
+impl Color {
+    pub const ALL: [Color; 8] = [
+        Color::White,
+        Color::Yellow,
+        Color::Red,
+        Color::Orange,
+        Color::Blue,
+        Color::Green,
+        Color::Other,
+        Color::Wildcard,
+    ];
+}
+
 // TODO 不要なはず
 // impl Color {
 //     pub fn from(c: char) -> Self {
@@ -0,0 +1,130 @@
+use crate::entities::Ops;
+
+/// Constraints on the moves of the solutions
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Constraints {
+    /// Turns not to be done in a row, by the layers and the amounts
+    /// (e.g. `F B` avoids F and then B, and `x x` avoids x2 but not x)
+    pub avoid: Vec<Ops>,
+    /// Moves to start with
+    pub prefix: Ops,
+    /// Moves to end with
+    pub suffix: Ops,
+    /// Max num of the turns of the layers in total (e.g. `x y z` up to 2)
+    pub limits: Vec<(Ops, usize)>,
+}
+
+/// Layers and the quarter turns (1 to 3, clockwise) of the turns (e.g. R2 is one turn)
+fn turns(ops: &Ops) -> Vec<(usize, usize)> {
+    let mut turns: Vec<(usize, usize)> = vec![];
+    for op in ops.expand().data.iter() {
+        let quarters = if op.is_reversed() { 3 } else { 1 };
+        match turns.last_mut() {
+            Some((layer, amount)) if *layer == op.layer() => {
+                *amount = (*amount + quarters) % 4;
+                if *amount == 0 {
+                    turns.pop();
+                }
+            }
+            _ => turns.push((op.layer(), quarters)),
+        }
+    }
+    turns
+}
+
+impl Constraints {
+    pub fn is_empty(&self) -> bool {
+        *self == Constraints::default()
+    }
+
+    /// Whether the whole algorithm fits
+    pub fn allows(&self, ops: &Ops) -> bool {
+        let ops = ops.expand();
+        let (prefix, suffix) = (self.prefix.expand(), self.suffix.expand());
+        ops.data.starts_with(&prefix.data)
+            && ops.data.ends_with(&suffix.data)
+            && self.fits(&ops, false)
+    }
+
+    /// Whether there are turns to avoid or limits, which prune the moves in the search
+    pub fn restricts_moves(&self) -> bool {
+        !self.avoid.is_empty() || !self.limits.is_empty()
+    }
+
+    /// Whether a part of the algorithm may fit: no turns to avoid, and within the limits.
+    /// The turns at the ends may be continued (e.g. F B to F B2), so they are not matched yet.
+    pub fn allows_part(&self, ops: &Ops) -> bool {
+        self.fits(ops, true)
+    }
+
+    fn fits(&self, ops: &Ops, part: bool) -> bool {
+        if !self.restricts_moves() {
+            return true;
+        }
+        let turns = turns(ops);
+        let n = turns.len();
+        let same = |i: usize, avoid: &(usize, usize)| {
+            let turn = turns[i];
+            turn == *avoid && !(part && (i == 0 || i + 1 == n))
+        };
+        for avoid in self.avoid.iter() {
+            let avoid = self::turns(avoid);
+            if !avoid.is_empty()
+                && n >= avoid.len()
+                && (0..=n - avoid.len())
+                    .any(|i| avoid.iter().enumerate().all(|(j, a)| same(i + j, a)))
+            {
+                return false;
+            }
+        }
+        for (moves, max) in self.limits.iter() {
+            let layers: Vec<usize> = self::turns(moves).iter().map(|t| t.0).collect();
+            if turns.iter().filter(|t| layers.contains(&t.0)).count() > *max {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod test_constraints {
+    use crate::entities::*;
+    use crate::read;
+
+    fn ops(s: &str) -> Ops {
+        read::parse_ops(s).unwrap().1
+    }
+
+    #[test]
+    fn test_constraints() {
+        let constraints = Constraints {
+            avoid: vec![ops("F B")],
+            prefix: ops("R U'"),
+            limits: vec![(ops("x y z"), 1)],
+            ..Constraints::default()
+        };
+        assert!(constraints.allows(&ops("R U' F U B")));
+        assert!(!constraints.allows(&ops("R U' F B")));
+        assert!(constraints.allows(&ops("R U' F2 B'")));
+        assert!(!constraints.allows(&ops("R U F U B")));
+        assert!(constraints.allows(&ops("R U' x2 R")));
+        assert!(!constraints.allows(&ops("R U' x R y")));
+        assert!(constraints.allows_part(&ops("F U B")));
+        assert!(!constraints.allows_part(&ops("x F B U")));
+        assert!(constraints.allows_part(&ops("x F B")));
+        assert!(Constraints::default().is_empty());
+
+        // The amounts are compared: x2 is avoided, but not a single x
+        let constraints = Constraints {
+            avoid: vec![ops("x x"), ops("R R")],
+            ..Constraints::default()
+        };
+        assert!(constraints.allows(&ops("R x R")));
+        assert!(constraints.allows(&ops("R U R'")));
+        assert!(!constraints.allows(&ops("R x2")));
+        assert!(!constraints.allows(&ops("U R2 U")));
+        assert!(constraints.allows_part(&ops("U R R")));
+        assert!(!constraints.allows_part(&ops("U R R U")));
+    }
+}
@@ -1,8 +1,19 @@
-use crate::entities::{Color, Face, FaceIndex, Operation};
+use crate::cube;
+use crate::entities::{Color, Face, FaceIndex, Operation, Permutation};
 use crate::rotate;
 use crate::util;
 use std::collections::BTreeSet;
 
+pub const NUM_STICKERS: usize = 54;
+const FACES: [FaceIndex; 6] = [
+    FaceIndex::Front,
+    FaceIndex::Back,
+    FaceIndex::Up,
+    FaceIndex::Down,
+    FaceIndex::Left,
+    FaceIndex::Right,
+];
+
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Cube {
     pub front: Face,
@@ -24,6 +35,20 @@ impl Cube {
             right,
         }
     }
+    /// The solved cube (yellow up, red front)
+    pub fn solved() -> Self {
+        cube![
+            Y Y Y;
+            Y Y Y;
+            Y Y Y;
+            R R R G G G O O O B B B;
+            R R R G G G O O O B B B;
+            R R R G G G O O O B B B;
+            W W W;
+            W W W;
+            W W W;
+        ]
+    }
     pub fn from(lines: Vec<Vec<Color>>) -> Self {
         let up = Face::from(lines[0..3].to_vec());
         let front = Face::from(
@@ -125,6 +150,58 @@ impl Cube {
             }
         }
     }
+    /// Index of the sticker (face by face in the order of FaceIndex, row by row)
+    pub fn sticker(face: FaceIndex, i: usize, j: usize) -> usize {
+        face as usize * 9 + i * 3 + j
+    }
+    pub fn stickers(&self) -> [Color; NUM_STICKERS] {
+        let mut stickers = [Color::Other; NUM_STICKERS];
+        for face in FACES {
+            for i in 0..3 {
+                for j in 0..3 {
+                    stickers[Self::sticker(face, i, j)] = self[face].at(i, j);
+                }
+            }
+        }
+        stickers
+    }
+    pub fn set_stickers(&mut self, stickers: &[Color; NUM_STICKERS]) {
+        for face in FACES {
+            for i in 0..3 {
+                for j in 0..3 {
+                    self[face][(i, j)] = stickers[Self::sticker(face, i, j)];
+                }
+            }
+        }
+    }
+    /// Relabel colors so that the centers agree with `reference`.
+    /// None if the centers are not distinct concrete colors.
+    pub fn recolor(&self, reference: &Cube) -> Option<Self> {
+        let mut table = BTreeMap::new();
+        for c in util::centers().iter() {
+            let from = self[c.0].at(c.1, c.2);
+            let to = reference[c.0].at(c.1, c.2);
+            for col in [from, to] {
+                if col == Color::Other || col == Color::Wildcard {
+                    return None;
+                }
+            }
+            if table.insert(from, to).is_some() {
+                return None;
+            }
+        }
+        let mut cube = self.clone();
+        for c in util::centers().iter() {
+            for i in 0..3 {
+                for j in 0..3 {
+                    if let Some(&col) = table.get(&self[c.0].at(i, j)) {
+                        cube[c.0][(i, j)] = col;
+                    }
+                }
+            }
+        }
+        Some(cube)
+    }
 }
 
 impl std::fmt::Display for Cube {
@@ -151,7 +228,14 @@ impl std::fmt::Display for Cube {
 }
 
 impl Cube {
+    /// Apply the operation as a precomputed permutation of the stickers
     pub fn apply(&mut self, op: &Operation) {
+        self.set_stickers(&Permutation::of(op).permute(&self.stickers()));
+    }
+
+    /// Apply by cycling the stickers.
+    /// This defines the moves (and the permutations), though slower than `apply`.
+    pub fn apply_by_cycles(&mut self, op: &Operation) {
         use Operation::*;
         match op {
             &Up(clockwise) => {
@@ -275,28 +359,28 @@ impl Cube {
                 );
             }
             &UpDouble(clockwise) => {
-                self.apply(&Up(clockwise));
-                self.apply(&Equator(!clockwise));
+                self.apply_by_cycles(&Up(clockwise));
+                self.apply_by_cycles(&Equator(!clockwise));
             }
             &DownDouble(clockwise) => {
-                self.apply(&Down(clockwise));
-                self.apply(&Equator(clockwise));
+                self.apply_by_cycles(&Down(clockwise));
+                self.apply_by_cycles(&Equator(clockwise));
             }
             &FrontDouble(clockwise) => {
-                self.apply(&Front(clockwise));
-                self.apply(&Standing(clockwise));
+                self.apply_by_cycles(&Front(clockwise));
+                self.apply_by_cycles(&Standing(clockwise));
             }
             &BackDouble(clockwise) => {
-                self.apply(&Back(clockwise));
-                self.apply(&Standing(!clockwise));
+                self.apply_by_cycles(&Back(clockwise));
+                self.apply_by_cycles(&Standing(!clockwise));
             }
             &LeftDouble(clockwise) => {
-                self.apply(&Left(clockwise));
-                self.apply(&Middle(clockwise));
+                self.apply_by_cycles(&Left(clockwise));
+                self.apply_by_cycles(&Middle(clockwise));
             }
             &RightDouble(clockwise) => {
-                self.apply(&Right(clockwise));
-                self.apply(&Middle(!clockwise));
+                self.apply_by_cycles(&Right(clockwise));
+                self.apply_by_cycles(&Middle(!clockwise));
             }
             &Middle(clockwise) => {
                 rotate!(
@@ -356,28 +440,28 @@ impl Cube {
                 );
             }
             &X(clockwise) => {
-                self.apply(&Right(clockwise));
-                self.apply(&Middle(!clockwise));
-                self.apply(&Left(!clockwise));
+                self.apply_by_cycles(&Right(clockwise));
+                self.apply_by_cycles(&Middle(!clockwise));
+                self.apply_by_cycles(&Left(!clockwise));
             }
             &Y(clockwise) => {
-                self.apply(&Up(clockwise));
-                self.apply(&Equator(!clockwise));
-                self.apply(&Down(!clockwise));
+                self.apply_by_cycles(&Up(clockwise));
+                self.apply_by_cycles(&Equator(!clockwise));
+                self.apply_by_cycles(&Down(!clockwise));
             }
             &Z(clockwise) => {
-                self.apply(&Front(clockwise));
-                self.apply(&Standing(clockwise));
-                self.apply(&Back(!clockwise));
+                self.apply_by_cycles(&Front(clockwise));
+                self.apply_by_cycles(&Standing(clockwise));
+                self.apply_by_cycles(&Back(!clockwise));
             }
             Compound(_, true, operations) => {
                 for op in operations.iter() {
-                    self.apply(op);
+                    self.apply_by_cycles(op);
                 }
             }
             Compound(_, false, operations) => {
                 for op in operations.iter().rev() {
-                    self.apply(&op.rev());
+                    self.apply_by_cycles(&op.rev());
                 }
             }
         }
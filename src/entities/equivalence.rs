@@ -0,0 +1,264 @@
+use crate::entities::{Cube, FaceIndex, Operation, Ops};
+use crate::util;
+
+/// What to ignore in `Ops::equivalent`
+#[derive(Debug, Clone, Default)]
+pub struct EquivalenceOptions {
+    /// The rotations of the whole cube before and after the algorithm
+    pub rotation: bool,
+    /// U moves before and after the algorithm
+    pub auf: bool,
+    /// The pieces out of the last layer (U layer)
+    pub last_layer: bool,
+}
+
+impl EquivalenceOptions {
+    pub fn all() -> Self {
+        Self {
+            rotation: true,
+            auf: true,
+            last_layer: true,
+        }
+    }
+}
+
+/// How two algorithms are the same, from the strictest
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Equivalence {
+    Identical,
+    ModuloRotation,
+    ModuloAuf,
+    ModuloAufAndRotation,
+    OnLastLayer,
+    OnLastLayerModuloAuf,
+}
+
+impl std::fmt::Display for Equivalence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use Equivalence::*;
+        let s = match self {
+            Identical => "identical",
+            ModuloRotation => "identical modulo rotation",
+            ModuloAuf => "identical modulo AUF",
+            ModuloAufAndRotation => "identical modulo AUF and rotation",
+            OnLastLayer => "identical on LL",
+            OnLastLayerModuloAuf => "identical on LL modulo AUF",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// The U moves for AUF (none, U, U2, U')
+fn aufs() -> Vec<Ops> {
+    use Operation::*;
+    vec![
+        Ops::default(),
+        Ops::new(vec![Up(true)]),
+        Ops::new(vec![Up(true), Up(true)]),
+        Ops::new(vec![Up(false)]),
+    ]
+}
+
+/// `pre ops post`
+fn wrap(pre: &Ops, ops: &Ops, post: &Ops) -> Ops {
+    let mut wrapped = pre.clone();
+    wrapped.extend(ops);
+    wrapped.extend(post);
+    wrapped
+}
+
+/// Same on the stickers of the last layer
+fn same_last_layer(c: &Cube, d: &Cube) -> bool {
+    let (c, d) = (c.stickers(), d.stickers());
+    let mut stickers = vec![];
+    for face in [
+        FaceIndex::Front,
+        FaceIndex::Back,
+        FaceIndex::Left,
+        FaceIndex::Right,
+    ] {
+        for j in 0..3 {
+            stickers.push(Cube::sticker(face, 0, j));
+        }
+    }
+    for i in 0..3 {
+        for j in 0..3 {
+            stickers.push(Cube::sticker(FaceIndex::Up, i, j));
+        }
+    }
+    stickers.into_iter().all(|k| c[k] == d[k])
+}
+
+/// The rotations and the AUFs to try within the options
+fn around(options: &EquivalenceOptions) -> (Vec<Ops>, Vec<Ops>) {
+    let rotations = if options.rotation {
+        util::rotations()
+    } else {
+        vec![Ops::default()]
+    };
+    let aufs = if options.auf {
+        aufs()
+    } else {
+        vec![Ops::default()]
+    };
+    (rotations, aufs)
+}
+
+impl Ops {
+    /// `self` followed by the inverse of each rotation:
+    /// `rotation other post` matches one of them if it is `self` up to the rotation after
+    fn targets(&self, rotations: &[Ops]) -> Vec<Cube> {
+        rotations
+            .iter()
+            .map(|r| wrap(&Ops::default(), self, &r.rev()).apply(&Cube::solved()))
+            .collect()
+    }
+
+    /// `other` with the rotations and the AUFs around it (within the options) which does
+    /// the same as `self`, the shortest one. None if they are not equivalent so.
+    pub fn aligned(&self, other: &Ops, options: &EquivalenceOptions) -> Option<Ops> {
+        let solved = Cube::solved();
+        let (rotations, aufs) = around(options);
+        let targets = self.targets(&rotations);
+        let mut best: Option<Ops> = None;
+        for rotation in rotations.iter() {
+            for pre in aufs.iter() {
+                for post in aufs.iter() {
+                    let wrapped = wrap(&wrap(rotation, pre, &Ops::default()), other, post);
+                    let d = wrapped.apply(&solved);
+                    let Some(k) = targets.iter().position(|t| *t == d) else {
+                        continue;
+                    };
+                    let wrapped = wrap(&Ops::default(), &wrapped, &rotations[k]);
+                    if best.as_ref().is_none_or(|best| wrapped.len() < best.len()) {
+                        best = Some(wrapped);
+                    }
+                }
+            }
+        }
+        best
+    }
+
+    /// The strictest equivalence of the effects on the solved cube, within the options.
+    /// None if the algorithms are different.
+    pub fn equivalent(&self, other: &Ops, options: &EquivalenceOptions) -> Option<Equivalence> {
+        let solved = Cube::solved();
+        let c = self.apply(&solved);
+        if other.apply(&solved) == c {
+            return Some(Equivalence::Identical);
+        }
+        let (rotations, aufs) = around(options);
+        let targets = self.targets(&rotations);
+        let mut by_rotation = false;
+        let mut by_auf = false;
+        let mut by_both = false;
+        let mut variants = vec![];
+        for rotation in rotations.iter() {
+            for pre in aufs.iter() {
+                for post in aufs.iter() {
+                    let d = wrap(&wrap(rotation, pre, &Ops::default()), other, post).apply(&solved);
+                    let aligned = pre.is_empty() && post.is_empty();
+                    if rotation.is_empty() {
+                        by_auf |= d == c;
+                        variants.push(d.clone());
+                    }
+                    if targets.contains(&d) {
+                        if aligned {
+                            by_rotation = true;
+                        } else {
+                            by_both = true;
+                        }
+                    }
+                }
+            }
+        }
+        if by_rotation {
+            return Some(Equivalence::ModuloRotation);
+        }
+        if by_auf {
+            return Some(Equivalence::ModuloAuf);
+        }
+        if by_both {
+            return Some(Equivalence::ModuloAufAndRotation);
+        }
+        if options.last_layer {
+            if same_last_layer(&variants[0], &c) {
+                return Some(Equivalence::OnLastLayer);
+            }
+            if variants.iter().any(|d| same_last_layer(d, &c)) {
+                return Some(Equivalence::OnLastLayerModuloAuf);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test_equivalence {
+    use crate::entities::*;
+    use crate::read;
+
+    fn equivalent(a: &str, b: &str) -> Option<Equivalence> {
+        let a = read::parse_ops(a).unwrap().1;
+        let b = read::parse_ops(b).unwrap().1;
+        a.equivalent(&b, &EquivalenceOptions::all())
+    }
+
+    #[test]
+    fn test_equivalent() {
+        use Equivalence::*;
+        assert_eq!(equivalent("R U R' U'", "R U R' U'"), Some(Identical));
+        // Sune from the other side, and with the AUF before and a rotation after
+        let sune = "R U R' U R U2 R'";
+        assert_eq!(
+            equivalent(&format!("y {}", sune), sune),
+            Some(ModuloRotation)
+        );
+        assert_eq!(
+            equivalent(sune, &format!("y {}", sune)),
+            Some(ModuloRotation)
+        );
+        assert_eq!(
+            equivalent(&format!("U {} y", sune), sune),
+            Some(ModuloAufAndRotation)
+        );
+        assert_eq!(
+            equivalent(sune, &format!("y U {}", sune)),
+            Some(ModuloAufAndRotation)
+        );
+        assert_eq!(equivalent("R L", "L R"), Some(Identical));
+        assert_eq!(equivalent("r", "L x"), Some(Identical));
+        assert_eq!(equivalent("M' U M", "M' U M x y"), Some(ModuloRotation));
+        // T perm, with the AUF before and after
+        let t = "R U R' U' R' F R2 F' U' R' U' R U R' F'";
+        assert_eq!(equivalent(t, &format!("U {} U'", t)), Some(ModuloAuf));
+        // Sune and the other Sune differ on F2L
+        assert_eq!(
+            equivalent("R U R' U R U2 R'", "R U R' U R U2 R' F R F'"),
+            None
+        );
+        // D moves only the first two layers
+        assert_eq!(equivalent("R U R'", "R U R' D"), Some(OnLastLayer));
+        assert_eq!(
+            equivalent("R U R' D", "U R U R'"),
+            Some(OnLastLayerModuloAuf)
+        );
+        assert_eq!(equivalent("R U R'", "R U' R'"), None);
+
+        // The AUFs and the rotations to do the same
+        let all = EquivalenceOptions::all();
+        let a = read::parse_ops(t).unwrap().1;
+        let b = read::parse_ops(&format!("U' {} U y", t)).unwrap().1;
+        let aligned = a.aligned(&b, &all).unwrap();
+        assert_eq!(aligned.apply(&Cube::solved()), a.apply(&Cube::solved()));
+        assert_eq!(aligned.len(), b.len() + 3);
+        assert!(a
+            .aligned(&read::parse_ops("R U R'").unwrap().1, &all)
+            .is_none());
+
+        let exact = EquivalenceOptions::default();
+        let a = read::parse_ops(t).unwrap().1;
+        let b = read::parse_ops(&format!("U {} U'", t)).unwrap().1;
+        assert_eq!(a.equivalent(&b, &exact), None);
+    }
+}
@@ -0,0 +1,185 @@
+use crate::entities::{Operation, Ops};
+
+/// Cost model of the finger tricks, in tenths of a move.
+/// The same moves in a row are one turn (e.g. U2), as in `Metric::Htm`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ergonomics {
+    /// Base costs of the quarter turns
+    pub up: usize,
+    pub down: usize,
+    pub front: usize,
+    pub back: usize,
+    pub left: usize,
+    pub right: usize,
+    /// M
+    pub middle: usize,
+    /// E and S
+    pub slice: usize,
+    /// Wide moves (e.g. r)
+    pub wide: usize,
+    /// x, y and z (the wrists go back to home)
+    pub rotation: usize,
+    /// Extra cost of a half turn
+    pub half_turn: usize,
+    /// Discount of R and U after R or U
+    pub ru_discount: usize,
+    /// Penalty when a wrist has to go back to home
+    pub regrip: usize,
+}
+
+impl Default for Ergonomics {
+    fn default() -> Self {
+        Self {
+            up: 8,
+            down: 12,
+            front: 14,
+            back: 20,
+            left: 12,
+            right: 8,
+            middle: 12,
+            slice: 20,
+            wide: 12,
+            rotation: 15,
+            half_turn: 4,
+            ru_discount: 2,
+            regrip: 15,
+        }
+    }
+}
+
+impl std::str::FromStr for Ergonomics {
+    type Err = String;
+    /// "default", or the costs to change from the default (e.g. "regrip=20,rotation=10")
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut ergonomics = Ergonomics::default();
+        if s == "default" {
+            return Ok(ergonomics);
+        }
+        for item in s.split(',') {
+            let (key, value) = item
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid cost: {} (e.g. regrip=20)", item))?;
+            let value: usize = value
+                .trim()
+                .parse()
+                .map_err(|_| format!("Invalid cost: {}", item))?;
+            let cost = match key.trim() {
+                "up" => &mut ergonomics.up,
+                "down" => &mut ergonomics.down,
+                "front" => &mut ergonomics.front,
+                "back" => &mut ergonomics.back,
+                "left" => &mut ergonomics.left,
+                "right" => &mut ergonomics.right,
+                "middle" => &mut ergonomics.middle,
+                "slice" => &mut ergonomics.slice,
+                "wide" => &mut ergonomics.wide,
+                "rotation" => &mut ergonomics.rotation,
+                "half_turn" => &mut ergonomics.half_turn,
+                "ru_discount" => &mut ergonomics.ru_discount,
+                "regrip" => &mut ergonomics.regrip,
+                _ => return Err(format!("Unknown cost: {}", key)),
+            };
+            *cost = value;
+        }
+        Ok(ergonomics)
+    }
+}
+
+/// Quarter turns of the wrists from home: -1 (e.g. after R') to 2 (after R2)
+const WRIST_RANGE: std::ops::RangeInclusive<i32> = -1..=2;
+
+impl Ops {
+    /// Cost of the finger tricks. The right wrist is turned by R and r, and the left
+    /// by L' and l'; a regrip is needed when it goes out of its range.
+    pub fn cost(&self, ergonomics: &Ergonomics) -> usize {
+        use Operation::*;
+        let ops = self.expand();
+        let mut cost = 0;
+        let mut wrists = [0i32; 2];
+        let mut ru = false;
+        let mut i = 0;
+        while i < ops.len() {
+            let op = &ops.data[i];
+            let mut repeat = 1;
+            while i + repeat < ops.len() && ops.data[i + repeat] == *op {
+                repeat += 1;
+            }
+            i += repeat;
+            let quarters = match repeat % 4 {
+                0 => continue,
+                3 => -1,
+                n => n as i32,
+            };
+            let base = match op {
+                Up(_) => ergonomics.up,
+                Down(_) => ergonomics.down,
+                Front(_) => ergonomics.front,
+                Back(_) => ergonomics.back,
+                Left(_) => ergonomics.left,
+                Right(_) => ergonomics.right,
+                Middle(_) => ergonomics.middle,
+                Equator(_) | Standing(_) => ergonomics.slice,
+                X(_) | Y(_) | Z(_) => {
+                    cost += ergonomics.rotation;
+                    wrists = [0, 0];
+                    ru = false;
+                    continue;
+                }
+                _ => ergonomics.wide,
+            };
+            cost += base;
+            if quarters == 2 {
+                cost += ergonomics.half_turn;
+            }
+            let is_ru = matches!(op, Up(_) | Right(_));
+            if is_ru && ru {
+                cost = cost.saturating_sub(ergonomics.ru_discount);
+            }
+            ru = is_ru;
+
+            let sign = if op.is_reversed() { -1 } else { 1 };
+            let wrist = match op {
+                Right(_) | RightDouble(_) => Some((0, sign * quarters)),
+                Left(_) | LeftDouble(_) => Some((1, -sign * quarters)),
+                _ => None,
+            };
+            if let Some((hand, turn)) = wrist {
+                if WRIST_RANGE.contains(&(wrists[hand] + turn)) {
+                    wrists[hand] += turn;
+                } else {
+                    cost += ergonomics.regrip;
+                    wrists[hand] = turn;
+                }
+            }
+        }
+        cost
+    }
+}
+
+#[cfg(test)]
+mod test_ergonomics {
+    use crate::entities::*;
+    use crate::read;
+
+    fn cost(s: &str) -> usize {
+        read::parse_ops(s).unwrap().1.cost(&Ergonomics::default())
+    }
+
+    #[test]
+    fn test_cost() {
+        // 8, and 6 for each R or U after R or U
+        assert_eq!(cost("R U R' U'"), 8 + 3 * 6);
+        assert_eq!(cost("U2"), 12);
+        assert!(cost("R U R' U'") < cost("F U F' U'"));
+        // R' R' is out of the range of the wrist
+        assert_eq!(cost("R' U R' U"), 8 + 3 * 6 + 15);
+        assert_eq!(cost("R' U R U R2"), 8 + 3 * 6 + 8 - 2 + 4);
+        assert!(cost("R U R' U' x") > cost("R U R' U'"));
+
+        let ergonomics: Ergonomics = "regrip=0, rotation=3".parse().unwrap();
+        assert_eq!(ergonomics.regrip, 0);
+        assert_eq!(ergonomics.rotation, 3);
+        assert!("regrip".parse::<Ergonomics>().is_err());
+        assert!("grip=1".parse::<Ergonomics>().is_err());
+    }
+}
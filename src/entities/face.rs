@@ -12,8 +12,8 @@ impl Face {
     }
     pub fn from(lines: Vec<Vec<Color>>) -> Self {
         let mut data = vec![];
-        for i in 0..3 {
-            data.push(lines[i].clone().try_into().unwrap());
+        for line in lines.iter().take(3) {
+            data.push(line.clone().try_into().unwrap());
         }
         let data: [[Color; 3]; 3] = data.try_into().unwrap();
         Self { data }
@@ -52,7 +52,7 @@ impl std::ops::Index<(usize, usize)> for Face {
 }
 
 impl std::ops::IndexMut<(usize, usize)> for Face {
-    fn index_mut<'a>(&'a mut self, index: (usize, usize)) -> &'a mut Color {
+    fn index_mut(&mut self, index: (usize, usize)) -> &mut Color {
         &mut self.data[index.0][index.1]
     }
 }
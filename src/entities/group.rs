@@ -0,0 +1,227 @@
+use crate::entities::{Ops, Permutation, Piece};
+
+/// Pieces moving in a cycle (each to the next, the last to the first)
+/// and the net twist: clockwise turns of a corner, or the flip of an edge
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cycle {
+    pub pieces: Vec<Piece>,
+    pub twist: usize,
+}
+
+impl Cycle {
+    pub fn is_corner(&self) -> bool {
+        self.pieces[0].is_corner()
+    }
+
+    fn twist_name(&self) -> &'static str {
+        match (self.is_corner(), self.twist) {
+            (_, 0) => "",
+            (true, 1) => "twisted clockwise",
+            (true, _) => "twisted counter-clockwise",
+            (false, _) => "flipped",
+        }
+    }
+}
+
+impl std::fmt::Display for Cycle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = if self.is_corner() { "corner" } else { "edge" };
+        if self.pieces.len() == 1 {
+            return write!(f, "{} {} {}", kind, self.pieces[0], self.twist_name());
+        }
+        let names: Vec<String> = self.pieces.iter().map(|p| p.name()).collect();
+        write!(
+            f,
+            "{}-cycle of {}s {}",
+            self.pieces.len(),
+            kind,
+            names.join("→")
+        )?;
+        if self.twist != 0 {
+            write!(f, " ({})", self.twist_name())?;
+        }
+        Ok(())
+    }
+}
+
+impl Permutation {
+    /// Num of repetitions to the identity
+    pub fn order(&self) -> usize {
+        fn gcd(a: usize, b: usize) -> usize {
+            if b == 0 {
+                a
+            } else {
+                gcd(b, a % b)
+            }
+        }
+        let identity = Permutation::identity();
+        let mut order = 1;
+        let mut visited = [false; 54];
+        for i in 0..visited.len() {
+            if visited[i] || identity.source(i) == self.source(i) {
+                continue;
+            }
+            let mut len = 0;
+            let mut k = i;
+            while !visited[k] {
+                visited[k] = true;
+                k = self.source(k);
+                len += 1;
+            }
+            order = order / gcd(order, len) * len;
+        }
+        order
+    }
+
+    /// Cycles of the corners and the edges (twisted or flipped pieces in place are
+    /// the cycles of one piece); the centers are ignored
+    pub fn cycles(&self) -> Vec<Cycle> {
+        let pieces = Piece::all();
+        // The piece at each position goes there
+        let dest: Vec<(Piece, usize)> = pieces.iter().map(|&p| Piece::at(p, self)).collect();
+        let goes_to = |piece: Piece| -> (Piece, usize) {
+            let i = dest.iter().position(|(p, _)| *p == piece).unwrap();
+            (pieces[i], dest[i].1)
+        };
+        let mut cycles = vec![];
+        let mut visited = vec![];
+        for &piece in pieces.iter() {
+            if visited.contains(&piece) {
+                continue;
+            }
+            let mut cycle = vec![];
+            let mut twist = 0;
+            let mut p = piece;
+            while !visited.contains(&p) {
+                visited.push(p);
+                cycle.push(p);
+                let (next, o) = goes_to(p);
+                twist += o;
+                p = next;
+            }
+            let n = piece.orientations();
+            // The orientation `o` is the facelet on the reference one; clockwise for corners
+            let twist = (n - twist % n) % n;
+            if cycle.len() > 1 || twist != 0 {
+                cycles.push(Cycle {
+                    pieces: cycle,
+                    twist,
+                });
+            }
+        }
+        cycles
+    }
+}
+
+impl Ops {
+    pub fn cycles(&self) -> Vec<Cycle> {
+        Permutation::of_ops(self).cycles()
+    }
+
+    /// Num of repetitions to the solved state
+    pub fn order(&self) -> usize {
+        Permutation::of_ops(self).order()
+    }
+
+    /// Split into `A B A' B'` (by the effects of the parts), if it is a commutator
+    pub fn commutator(&self) -> Option<(Ops, Ops)> {
+        let ops = self.expand();
+        let n = ops.len();
+        if !n.is_multiple_of(2) {
+            return None;
+        }
+        for a in 1..n / 2 {
+            let b = n / 2 - a;
+            let part = |i: usize, j: usize| Ops::new(ops.data[i..j].to_vec());
+            let (pa, pb) = (part(0, a), part(a, a + b));
+            let (qa, qb) = (part(a + b, a + b + a), part(a + b + a, n));
+            if Permutation::of_ops(&qa) == Permutation::of_ops(&pa).inverse()
+                && Permutation::of_ops(&qb) == Permutation::of_ops(&pb).inverse()
+            {
+                return Some((pa, pb));
+            }
+        }
+        None
+    }
+
+    pub fn is_pure_commutator(&self) -> bool {
+        self.commutator().is_some()
+    }
+}
+
+#[cfg(test)]
+mod test_group {
+    use crate::entities::*;
+    use crate::read;
+
+    fn ops(s: &str) -> Ops {
+        read::parse_ops(s).unwrap().1
+    }
+
+    fn cycles(s: &str) -> Vec<String> {
+        ops(s).cycles().iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn test_cycles() {
+        assert!(cycles("R R'").is_empty());
+        assert_eq!(
+            cycles("U"),
+            vec![
+                "4-cycle of corners UFL→ULB→UBR→URF",
+                "4-cycle of edges UF→UL→UB→UR"
+            ]
+        );
+        // Ua perm
+        assert_eq!(
+            cycles("R U' R U R U R U' R' U' R2"),
+            vec!["3-cycle of edges UF→UR→UL"]
+        );
+        // Sune (and U2) twists 3 corners, and cycles 3 edges
+        assert_eq!(
+            cycles("R U R' U R U2 R' U2"),
+            vec![
+                "corner URF twisted clockwise",
+                "corner ULB twisted clockwise",
+                "corner UBR twisted clockwise",
+                "3-cycle of edges UF→UB→UR"
+            ]
+        );
+        // (M' U)4 (M U)4 flips the 4 edges of U
+        assert_eq!(
+            cycles("M' U M' U M' U M' U M U M U M U M U"),
+            vec![
+                "edge UF flipped",
+                "edge UR flipped",
+                "edge UB flipped",
+                "edge UL flipped"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_order() {
+        assert_eq!(ops("R").order(), 4);
+        assert_eq!(ops("R U").order(), 105);
+        assert_eq!(ops("R U R' U'").order(), 6);
+        assert_eq!(ops("R R'").order(), 1);
+    }
+
+    #[test]
+    fn test_commutator() {
+        let (a, b) = ops("R U R' U'").commutator().unwrap();
+        assert_eq!(
+            (a.to_string(), b.to_string()),
+            ("R".to_string(), "U".to_string())
+        );
+        // A9 commutator of a corner 3-cycle
+        let (a, b) = ops("R' D' R U R' D R U'").commutator().unwrap();
+        assert_eq!(
+            (a.to_string(), b.to_string()),
+            ("R'D'R".to_string(), "U".to_string())
+        );
+        assert!(ops("R2 U R2 U'").is_pure_commutator());
+        assert!(!ops("R U R' U").is_pure_commutator());
+        assert!(!ops("R U R'").is_pure_commutator());
+    }
+}
@@ -0,0 +1,90 @@
+use crate::entities::{Operation, Ops};
+
+/// How to count the length of the algorithms. The same moves in a row are one turn
+/// (e.g. U2) except in QTM.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Metric {
+    /// Half turn metric: a slice is two turns, and rotations are free
+    #[default]
+    Htm,
+    /// Quarter turn metric: a half turn is two turns
+    Qtm,
+    /// Slice turn metric: a slice is one turn
+    Stm,
+    /// Execution turn metric: the rotations are also turns
+    Etm,
+}
+
+impl std::str::FromStr for Metric {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "htm" => Ok(Metric::Htm),
+            "qtm" => Ok(Metric::Qtm),
+            "stm" => Ok(Metric::Stm),
+            "etm" => Ok(Metric::Etm),
+            _ => Err(format!("Invalid metric: {} (htm, qtm, stm or etm)", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for Metric {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Metric::Htm => "HTM",
+            Metric::Qtm => "QTM",
+            Metric::Stm => "STM",
+            Metric::Etm => "ETM",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl Ops {
+    /// Length in the metric
+    pub fn length_in(&self, metric: Metric) -> usize {
+        use Operation::*;
+        let ops = self.expand();
+        let mut length = 0;
+        let mut i = 0;
+        while i < ops.len() {
+            let op = &ops.data[i];
+            let mut repeat = 1;
+            while i + repeat < ops.len() && ops.data[i + repeat] == *op {
+                repeat += 1;
+            }
+            i += repeat;
+            let (slice, rotation) = match op {
+                Middle(_) | Equator(_) | Standing(_) => (true, false),
+                X(_) | Y(_) | Z(_) => (false, true),
+                _ => (false, false),
+            };
+            length += match metric {
+                Metric::Qtm if rotation => 0,
+                Metric::Qtm if slice => 2 * repeat,
+                Metric::Qtm => repeat,
+                Metric::Htm if rotation => 0,
+                Metric::Htm if slice => 2,
+                Metric::Stm if rotation => 0,
+                _ => 1,
+            };
+        }
+        length
+    }
+}
+
+#[cfg(test)]
+mod test_metric {
+    use crate::entities::*;
+    use crate::read;
+
+    #[test]
+    fn test_metric() {
+        let ops = read::parse_ops("R U2 R' M2 x r'").unwrap().1;
+        assert_eq!(ops.length_in(Metric::Htm), 6);
+        assert_eq!(ops.length_in(Metric::Qtm), 9);
+        assert_eq!(ops.length_in(Metric::Stm), 5);
+        assert_eq!(ops.length_in(Metric::Etm), 6);
+        assert_eq!("stm".parse::<Metric>(), Ok(Metric::Stm));
+    }
+}
@@ -1,10 +1,34 @@
 mod color;
+mod constraints;
 mod cube;
+mod equivalence;
+mod ergonomics;
 mod face;
 mod faceindex;
+mod group;
+mod metric;
+mod niss;
 mod operation;
-pub use self::cube::Cube;
+mod packed;
+mod permutation;
+mod piece;
+mod simplify;
+mod symmetry;
+mod transform;
+pub use self::cube::{Cube, NUM_STICKERS};
 pub use color::Color;
+pub use constraints::Constraints;
+pub use equivalence::{Equivalence, EquivalenceOptions};
+pub use ergonomics::Ergonomics;
 pub use face::Face;
 pub use faceindex::FaceIndex;
+pub use group::Cycle;
+pub use metric::Metric;
+pub use niss::Niss;
 pub use operation::{Operation, Ops};
+pub use packed::{PackedCube, PackedHashMap};
+pub use permutation::Permutation;
+pub use piece::Piece;
+pub use simplify::SimplifyOptions;
+pub use symmetry::Symmetry;
+pub use transform::MirrorAxis;
@@ -0,0 +1,123 @@
+use crate::entities::{Cube, Ops};
+
+/// Moves found on the normal scramble and on the inverse scramble, in the order
+/// they are found (e.g. `R U (F' D) L`: the parenthesized ones are on the inverse)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Niss {
+    /// The moves, and whether they are on the inverse
+    pub segments: Vec<(Ops, bool)>,
+}
+
+impl Niss {
+    pub fn push(&mut self, ops: Ops, inverse: bool) {
+        if !ops.is_empty() {
+            self.segments.push((ops, inverse));
+        }
+    }
+
+    /// All the moves on the normal scramble
+    pub fn normal(&self) -> Ops {
+        self.moves(false)
+    }
+
+    /// All the moves on the inverse scramble
+    pub fn inverse(&self) -> Ops {
+        self.moves(true)
+    }
+
+    fn moves(&self, inverse: bool) -> Ops {
+        let mut ops = Ops::default();
+        for (segment, on_inverse) in self.segments.iter() {
+            if *on_inverse == inverse {
+                ops.extend(segment);
+            }
+        }
+        ops
+    }
+
+    /// The linear solution: the normal moves, and then the inverse moves reversed
+    /// (cancelled where they meet)
+    pub fn solution(&self) -> Ops {
+        let mut ops = self.normal();
+        ops.extend(&self.inverse().rev());
+        ops.shorten()
+    }
+
+    /// The state to continue from on the normal or the inverse scramble.
+    /// On the normal, the inverse moves are premoves (`I' S N`), and vice versa (`N' S' I`).
+    pub fn apply(&self, scramble: &Ops, goal: &Cube, inverse: bool) -> Cube {
+        let (scramble, moves, premoves) = if inverse {
+            (scramble.rev(), self.inverse(), self.normal())
+        } else {
+            (scramble.clone(), self.normal(), self.inverse())
+        };
+        let mut ops = premoves.rev();
+        ops.extend(&scramble);
+        ops.extend(&moves);
+        ops.apply(goal)
+    }
+
+    /// Whether the combined solution solves the scramble
+    pub fn solves(&self, scramble: &Ops, goal: &Cube) -> bool {
+        self.solution().apply(&scramble.apply(goal)) == *goal
+    }
+}
+
+impl std::fmt::Display for Niss {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.segments.is_empty() {
+            return write!(f, "(nop)");
+        }
+        for (ops, inverse) in self.segments.iter() {
+            if *inverse {
+                write!(f, "({})", ops)?;
+            } else {
+                write!(f, "{}", ops)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test_niss {
+    use crate::entities::*;
+    use crate::read;
+
+    fn ops(s: &str) -> Ops {
+        read::parse_ops(s).unwrap().1
+    }
+
+    #[test]
+    fn test_niss() {
+        let scramble = ops("R U F");
+        let goal = Cube::solved();
+        let niss = read::parse_niss("(R U) F'").unwrap().1;
+        assert_eq!(niss.to_string(), "(RU)F'");
+        assert_eq!(niss.normal(), ops("F'"));
+        assert_eq!(niss.inverse(), ops("R U"));
+        assert_eq!(niss.solution(), ops("F' U' R'"));
+        assert!(niss.solves(&scramble, &goal));
+
+        // Only `(R)` is done: U F remains on the normal, and F' U' on the inverse
+        let mut partial = Niss::default();
+        partial.push(ops("R"), true);
+        assert_eq!(
+            partial.apply(&scramble, &goal, false),
+            ops("R' R U F").apply(&goal)
+        );
+        assert_eq!(
+            partial.apply(&scramble, &goal, true),
+            ops("F' U' R' R").apply(&goal)
+        );
+        assert!(!partial.solves(&scramble, &goal));
+        partial.push(ops("U"), true);
+        partial.push(ops("F'"), false);
+        assert_eq!(partial.solution(), niss.solution());
+        assert_eq!(partial.to_string(), "(R)(U)F'");
+
+        partial.push(ops("R"), false);
+        partial.push(ops("R"), true);
+        assert_eq!(partial.solution(), niss.solution());
+    }
+}
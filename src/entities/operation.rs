@@ -77,11 +77,56 @@ impl Operation {
         }
     }
     pub fn is_compound(&self) -> bool {
+        matches!(self, Operation::Compound(_, _, _))
+    }
+    pub fn is_rotation(&self) -> bool {
+        matches!(self, Operation::X(_) | Operation::Y(_) | Operation::Z(_))
+    }
+    /// Axis of the turn (0: U-D, 1: F-B, 2: L-R), None for the compound ones.
+    /// The turns on the same axis commute.
+    pub fn axis(&self) -> Option<usize> {
+        use Operation::*;
         match self {
-            Operation::Compound(_, _, _) => true,
-            _ => false,
+            Up(_) | Down(_) | UpDouble(_) | DownDouble(_) | Equator(_) | Y(_) => Some(0),
+            Front(_) | Back(_) | FrontDouble(_) | BackDouble(_) | Standing(_) | Z(_) => Some(1),
+            Left(_) | Right(_) | LeftDouble(_) | RightDouble(_) | Middle(_) | X(_) => Some(2),
+            Compound(_, _, _) => None,
         }
     }
+    /// Order of the layers in the canonical sequence (the direction is ignored)
+    pub(crate) fn layer(&self) -> usize {
+        use Operation::*;
+        match self {
+            Up(_) => 0,
+            Down(_) => 1,
+            Front(_) => 2,
+            Back(_) => 3,
+            Left(_) => 4,
+            Right(_) => 5,
+            UpDouble(_) => 6,
+            DownDouble(_) => 7,
+            FrontDouble(_) => 8,
+            BackDouble(_) => 9,
+            LeftDouble(_) => 10,
+            RightDouble(_) => 11,
+            Middle(_) => 12,
+            Equator(_) => 13,
+            Standing(_) => 14,
+            X(_) => 15,
+            Y(_) => 16,
+            Z(_) => 17,
+            Compound(_, _, _) => 18,
+        }
+    }
+    /// `self` then `next` commute and are not in the canonical order
+    /// (e.g. D U, which is same to U D)
+    pub fn is_unordered(&self, next: &Operation) -> bool {
+        self.commutes(next) && self.layer() > next.layer()
+    }
+    /// The other layer on the same axis (e.g. U and D, or R and x)
+    pub fn commutes(&self, other: &Operation) -> bool {
+        self.axis().is_some() && self.axis() == other.axis() && self.layer() != other.layer()
+    }
 }
 
 impl std::fmt::Display for Operation {
@@ -129,11 +174,11 @@ impl std::fmt::Display for Operation {
                 Z(true) => "z",
                 Z(false) => "z'",
                 Compound(name, true, _) => {
-                    note = format!("({})", name.to_string());
+                    note = format!("({})", name);
                     &note
                 }
                 Compound(name, false, _) => {
-                    note = format!("({})'", name.to_string());
+                    note = format!("({})'", name);
                     &note
                 }
             }
@@ -160,6 +205,9 @@ impl Ops {
     pub fn len(&self) -> usize {
         self.data.len()
     }
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
     /// length of expanded
     pub fn weight(&self) -> usize {
         use Operation::*;
@@ -222,32 +270,47 @@ impl Ops {
         }
         ops
     }
+    /// The U moves at the beginning (AUF before), the rest, and the U moves at the end
+    /// (AUF after). All goes to the last for only the U moves. The compounds are kept.
+    pub fn trim_auf(&self) -> (Self, Self, Self) {
+        use Operation::*;
+        let ops = self;
+        let is_auf = |op: &Operation| matches!(op, Up(_));
+        let end = ops.len() - ops.data.iter().rev().take_while(|op| is_auf(op)).count();
+        let begin = ops.data[..end].iter().take_while(|op| is_auf(op)).count();
+        (
+            Ops::new(ops.data[..begin].to_vec()),
+            Ops::new(ops.data[begin..end].to_vec()),
+            Ops::new(ops.data[end..].to_vec()),
+        )
+    }
+    /// Cancel the redundant moves, also over the commuting moves (e.g. D U D' U -> U2).
+    /// The moves are kept in the order unless they are cancelled or merged.
     pub fn shorten(&self) -> Self {
         let mut ops = Ops::default();
         for op in self.data.iter() {
-            ops.push(op.clone());
-            let m = ops.len();
-            if m >= 3 {
-                if !ops.data[m - 1].is_compound() {
-                    let a = ops.data[m - 1].clone();
-                    let b = ops.data[m - 2].clone();
-                    let c = ops.data[m - 3].clone();
-                    if a == b && a == c {
-                        ops.pop();
-                        ops.pop();
-                        ops.pop();
-                        ops.push(a.rev());
-                    }
+            let mut op = op.clone();
+            let mut i = ops.len();
+            loop {
+                // The same layer behind the commuting moves
+                let mut j = i;
+                while j > 0 && ops.data[j - 1].commutes(&op) {
+                    j -= 1;
                 }
-            }
-            let m = ops.len();
-            if m >= 2 {
-                let a = ops.data[m - 1].clone();
-                let b = ops.data[m - 2].clone();
-                if a == b.rev() {
-                    ops.pop();
-                    ops.pop();
+                if j >= 2 && !op.is_compound() && ops.data[j - 1] == op && ops.data[j - 2] == op {
+                    ops.data.drain(j - 2..j);
+                    i = j - 2;
+                    op = op.rev();
+                    continue;
+                }
+                if j >= 1 && ops.data[j - 1] == op.rev() {
+                    ops.data.remove(j - 1);
+                } else if j >= 1 && ops.data[j - 1] == op && !op.is_compound() {
+                    ops.data.insert(j, op);
+                } else {
+                    ops.data.insert(i, op);
                 }
+                break;
             }
         }
         ops
@@ -256,7 +319,7 @@ impl Ops {
 
 impl std::fmt::Display for Ops {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if self.data.is_empty() {
+        if self.is_empty() {
             write!(f, "(nop)")
         } else {
             for op in self.data.iter() {
@@ -280,6 +343,23 @@ mod test_operation {
         assert!(u_prime.is_reversed());
     }
 
+    #[test]
+    fn test_trim_auf() {
+        let ops = |s: &str| crate::read::parse_ops(s).unwrap().1;
+        assert_eq!(
+            ops("U R U R' U2").trim_auf(),
+            (ops("U"), ops("R U R'"), ops("U U"))
+        );
+        assert_eq!(
+            ops("U'").trim_auf(),
+            (Ops::default(), Ops::default(), ops("U'"))
+        );
+        // The compounds are kept
+        let sexy = Compound("Sx".to_string(), true, ops("R U R' U'").data);
+        let (pre, alg, post) = Ops::new(vec![Up(true), sexy.clone(), Up(true)]).trim_auf();
+        assert_eq!((pre, alg, post), (ops("U"), Ops::new(vec![sexy]), ops("U")));
+    }
+
     #[test]
     fn test_ops() {
         let mut ops = Ops::default();
@@ -298,4 +378,31 @@ mod test_operation {
         assert_eq!(ops.last(), Some(Up(false)));
         assert_eq!(ops.last_repeat(), None);
     }
+
+    #[test]
+    fn test_shorten() {
+        let ops = Ops::new(vec![Down(true), Up(true), Down(false), Up(true)]);
+        assert_eq!(ops.shorten(), Ops::new(vec![Up(true), Up(true)]));
+        let ops = Ops::new(vec![Right(true), Left(true), Middle(true), Right(true)]);
+        assert_eq!(
+            ops.shorten(),
+            Ops::new(vec![Right(true), Right(true), Left(true), Middle(true)])
+        );
+        let ops = Ops::new(vec![Up(true), Up(true), Down(true), Up(true), Front(true)]);
+        assert_eq!(
+            ops.shorten(),
+            Ops::new(vec![Up(false), Down(true), Front(true)])
+        );
+        let ops = Ops::new(vec![Front(true), Back(true), Up(true), Back(false)]);
+        assert_eq!(
+            ops.shorten(),
+            Ops::new(vec![Front(true), Back(true), Up(true), Back(false)])
+        );
+        // Nothing to cancel: the order is kept
+        let ops = Ops::new(vec![Right(true), Left(true), Up(true), Down(true)]);
+        assert_eq!(ops.shorten(), ops);
+        assert!(Down(true).is_unordered(&Up(false)));
+        assert!(!Up(true).is_unordered(&Down(true)));
+        assert!(!Down(true).is_unordered(&Right(true)));
+    }
 }
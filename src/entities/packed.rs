@@ -0,0 +1,205 @@
+use crate::entities::cube::NUM_STICKERS;
+use crate::entities::{Color, Cube, Face, Permutation};
+use std::hash::{BuildHasherDefault, Hash, Hasher};
+
+const BITS: usize = 3;
+const MASK: u64 = (1 << BITS) - 1;
+/// Num of stickers in a word
+const STICKERS_PER_WORD: usize = 64 / BITS;
+/// The lowest bit of each sticker in a word
+const LOW_BITS: u64 = 0x1249_2492_4924_9249;
+
+/// Cube packed in 3 bits per sticker (54 stickers in 3 words),
+/// to be hashed and compared fast in the search.
+/// The first stickers are in the higher bits, so that the order is the same as of `Cube`
+/// (the equal weights in the search are broken by it).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PackedCube([u64; 3]);
+
+impl Hash for PackedCube {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for &word in self.0.iter() {
+            state.write_u64(word);
+        }
+    }
+}
+
+impl PackedCube {
+    /// Position of the sticker `i` in its word
+    fn shift(i: usize) -> usize {
+        (STICKERS_PER_WORD - 1 - i % STICKERS_PER_WORD) * BITS
+    }
+    fn get(&self, i: usize) -> u64 {
+        (self.0[i / STICKERS_PER_WORD] >> Self::shift(i)) & MASK
+    }
+    fn set(&mut self, i: usize, col: Color) {
+        self.0[i / STICKERS_PER_WORD] |= (col as u64) << Self::shift(i);
+    }
+    pub fn at(&self, sticker: usize) -> Color {
+        Color::ALL[self.get(sticker) as usize]
+    }
+    pub fn pack(cube: &Cube) -> Self {
+        let mut packed = Self([0; 3]);
+        for (i, &col) in cube.stickers().iter().enumerate() {
+            packed.set(i, col);
+        }
+        packed
+    }
+    pub fn unpack(&self) -> Cube {
+        let face = Face::new([[Color::Other; 3]; 3]);
+        let mut cube = Cube::new(
+            face.clone(),
+            face.clone(),
+            face.clone(),
+            face.clone(),
+            face.clone(),
+            face,
+        );
+        let mut stickers = [Color::Other; NUM_STICKERS];
+        for (i, col) in stickers.iter_mut().enumerate() {
+            *col = self.at(i);
+        }
+        cube.set_stickers(&stickers);
+        cube
+    }
+    /// Apply the permutation of an operation
+    pub fn permute(&self, perm: &Permutation) -> Self {
+        Self::from_raw(&perm.permute(&self.raw()))
+    }
+    /// Apply the permutation, and then replace the colors by the table
+    pub fn permute_recolor(&self, perm: &Permutation, colors: &[Color; 8]) -> Self {
+        let stickers = perm.permute(&self.raw()).map(|x| colors[x as usize] as u8);
+        Self::from_raw(&stickers)
+    }
+    /// The smallest of `permute_recolor` by each of the tables (by the stickers in order),
+    /// and the index of the first table to it.
+    /// Each sticker is computed only while it may be the smallest.
+    pub fn min_recolored<'a, I>(&self, tables: I) -> (Self, usize)
+    where
+        I: IntoIterator<Item = (&'a Permutation, &'a [Color; 8])>,
+    {
+        let raw = self.raw();
+        let mut min: Option<([u8; NUM_STICKERS], usize)> = None;
+        'tables: for (s, (perm, colors)) in tables.into_iter().enumerate() {
+            let sticker = |i: usize| colors[raw[perm.source(i)] as usize] as u8;
+            let Some((best, best_s)) = min.as_mut() else {
+                min = Some((std::array::from_fn(sticker), s));
+                continue;
+            };
+            let mut i = 0;
+            loop {
+                if i == NUM_STICKERS {
+                    continue 'tables;
+                }
+                let x = sticker(i);
+                if x > best[i] {
+                    continue 'tables;
+                }
+                if x < best[i] {
+                    break;
+                }
+                i += 1;
+            }
+            for (j, x) in best.iter_mut().enumerate().skip(i) {
+                *x = sticker(j);
+            }
+            *best_s = s;
+        }
+        match min {
+            Some((best, s)) => (Self::from_raw(&best), s),
+            None => (*self, 0),
+        }
+    }
+    /// Colors as the raw numbers
+    fn raw(&self) -> [u8; NUM_STICKERS] {
+        let mut stickers = [0; NUM_STICKERS];
+        for (chunk, &word) in stickers.chunks_mut(STICKERS_PER_WORD).zip(self.0.iter()) {
+            for (k, x) in chunk.iter_mut().enumerate() {
+                *x = ((word >> Self::shift(k)) & MASK) as u8;
+            }
+        }
+        stickers
+    }
+    fn from_raw(stickers: &[u8; NUM_STICKERS]) -> Self {
+        let mut packed = Self([0; 3]);
+        for (chunk, word) in stickers.chunks(STICKERS_PER_WORD).zip(packed.0.iter_mut()) {
+            for (k, &x) in chunk.iter().enumerate() {
+                *word |= (x as u64) << Self::shift(k);
+            }
+        }
+        packed
+    }
+    /// Same as `Cube::matched`, word by word
+    pub fn matched(&self, other: &PackedCube) -> bool {
+        // All the bits of the wildcard stickers in the word
+        let wildcards = |word: u64| {
+            let x = word ^ (LOW_BITS * Color::Wildcard as u64);
+            let concrete = (x | x >> 1 | x >> 2) & LOW_BITS;
+            (!concrete & LOW_BITS) * MASK
+        };
+        self.0.iter().zip(other.0.iter()).all(|(&a, &b)| {
+            let masked = wildcards(a) | wildcards(b);
+            (a ^ b) & !masked == 0
+        })
+    }
+}
+
+/// FxHash-like hasher for PackedCube (the default SipHash is slow for the search)
+#[derive(Default)]
+pub struct PackedHasher(u64);
+
+impl Hasher for PackedHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.write_u64(b as u64);
+        }
+    }
+    fn write_u64(&mut self, i: u64) {
+        self.0 = (self.0.rotate_left(5) ^ i).wrapping_mul(0x51_7c_c1_b7_27_22_0a_95);
+    }
+}
+
+pub type PackedHashMap<V> =
+    std::collections::HashMap<PackedCube, V, BuildHasherDefault<PackedHasher>>;
+
+#[cfg(test)]
+mod test_packed {
+    use crate::entities::*;
+    use crate::read;
+
+    #[test]
+    fn test_pack() {
+        let (cube, goal) = read::read("Scramble { R U F' M }");
+        let packed = PackedCube::pack(&cube);
+        assert_eq!(packed.unpack(), cube);
+        assert_ne!(packed, PackedCube::pack(&goal));
+        assert!(packed.matched(&packed));
+        assert!(!packed.matched(&PackedCube::pack(&goal)));
+        assert_eq!(
+            packed.cmp(&PackedCube::pack(&goal)),
+            cube.cmp(&goal),
+            "Same order as Cube"
+        );
+
+        let (cube, goal) = read::read(
+            "
+            Scramble { M }
+            Goal {
+              Y*Y
+              Y*Y
+              Y*Y
+              R*RGGGO*OBBB
+              R*RGGGO*OBBB
+              R*RGGGO*OBBB
+              W*W
+              W*W
+              W*W
+            }
+            ",
+        );
+        assert!(PackedCube::pack(&cube).matched(&PackedCube::pack(&goal)));
+    }
+}
@@ -0,0 +1,271 @@
+use crate::entities::cube::NUM_STICKERS;
+use crate::entities::{Color, Cube, Face, FaceIndex, Operation, Ops};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+/// Permutation of the stickers by an operation.
+/// The sticker `i` after the operation is the sticker `self.0[i]` before.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Permutation([u8; NUM_STICKERS]);
+
+thread_local! {
+    /// Permutations of the compound operations, by their operations
+    static COMPOUNDS: RefCell<BTreeMap<Vec<Operation>, Permutation>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+/// Num of the basic (not compound) operations
+const NUM_BASIC: usize = 36;
+
+impl Permutation {
+    pub fn identity() -> Self {
+        let mut data = [0; NUM_STICKERS];
+        for (i, x) in data.iter_mut().enumerate() {
+            *x = i as u8;
+        }
+        Self(data)
+    }
+
+    /// From the sources of the stickers. None if it is not a permutation.
+    pub fn from_sources(sources: &[u8; NUM_STICKERS]) -> Option<Self> {
+        let mut used = [false; NUM_STICKERS];
+        for &k in sources.iter() {
+            if k as usize >= NUM_STICKERS || used[k as usize] {
+                return None;
+            }
+            used[k as usize] = true;
+        }
+        Some(Self(*sources))
+    }
+
+    pub fn sources(&self) -> &[u8; NUM_STICKERS] {
+        &self.0
+    }
+
+    /// The source of the sticker `i`
+    pub fn source(&self, i: usize) -> usize {
+        self.0[i] as usize
+    }
+
+    /// `self` then `other`
+    pub fn then(&self, other: &Permutation) -> Self {
+        let mut data = [0; NUM_STICKERS];
+        for (i, x) in data.iter_mut().enumerate() {
+            *x = self.0[other.source(i)];
+        }
+        Self(data)
+    }
+
+    pub fn inverse(&self) -> Self {
+        let mut data = [0; NUM_STICKERS];
+        for i in 0..NUM_STICKERS {
+            data[self.source(i)] = i as u8;
+        }
+        Self(data)
+    }
+
+    /// Permutation of `other` seen through `self` (self^-1 other self)
+    pub fn conjugate(&self, other: &Permutation) -> Self {
+        self.inverse().then(other).then(self)
+    }
+
+    /// Mirror between the left and the right
+    pub fn mirror() -> Self {
+        use FaceIndex::*;
+        let mut data = [0; NUM_STICKERS];
+        for face in [Front, Back, Up, Down, Left, Right] {
+            let mirrored = match face {
+                Left => Right,
+                Right => Left,
+                _ => face,
+            };
+            for i in 0..3 {
+                for j in 0..3 {
+                    data[Cube::sticker(face, i, j)] = Cube::sticker(mirrored, i, 2 - j) as u8;
+                }
+            }
+        }
+        Self(data)
+    }
+
+    pub fn permute<T: Copy>(&self, stickers: &[T; NUM_STICKERS]) -> [T; NUM_STICKERS] {
+        let mut permuted = *stickers;
+        for (i, x) in permuted.iter_mut().enumerate() {
+            *x = stickers[self.source(i)];
+        }
+        permuted
+    }
+
+    /// Permutation of the operation (compound operations are composed into one,
+    /// once per thread)
+    pub fn of(op: &Operation) -> Self {
+        use Operation::*;
+        match op {
+            Compound(_, clockwise, operations) => {
+                let cached = COMPOUNDS.with(|map| map.borrow().get(operations.as_slice()).copied());
+                let perm = cached.unwrap_or_else(|| {
+                    let perm = operations
+                        .iter()
+                        .fold(Self::identity(), |perm, op| perm.then(&Self::of(op)));
+                    COMPOUNDS.with(|map| map.borrow_mut().insert(operations.clone(), perm));
+                    perm
+                });
+                if *clockwise {
+                    perm
+                } else {
+                    perm.inverse()
+                }
+            }
+            _ => basic_table()[basic_index(op)],
+        }
+    }
+
+    /// The basic (not compound) operation of the permutation, if any
+    pub fn basic(&self) -> Option<Operation> {
+        basic_ops()
+            .into_iter()
+            .find(|op| basic_table()[basic_index(op)] == *self)
+    }
+
+    /// Permutation of the sequence of operations
+    pub fn of_ops(ops: &Ops) -> Self {
+        ops.data
+            .iter()
+            .fold(Self::identity(), |perm, op| perm.then(&Self::of(op)))
+    }
+
+    /// Trace each sticker with `Cube::apply_by_cycles`
+    fn by_cycles(op: &Operation) -> Self {
+        let face = Face::new([[Color::Other; 3]; 3]);
+        let blank = Cube::new(
+            face.clone(),
+            face.clone(),
+            face.clone(),
+            face.clone(),
+            face.clone(),
+            face,
+        );
+        let mut data = [0; NUM_STICKERS];
+        for i in 0..NUM_STICKERS {
+            let mut stickers = blank.stickers();
+            stickers[i] = Color::White;
+            let mut cube = blank.clone();
+            cube.set_stickers(&stickers);
+            cube.apply_by_cycles(op);
+            let j = cube
+                .stickers()
+                .iter()
+                .position(|&col| col == Color::White)
+                .unwrap();
+            data[j] = i as u8;
+        }
+        Self(data)
+    }
+}
+
+fn basic_index(op: &Operation) -> usize {
+    use Operation::*;
+    let (k, clockwise) = match *op {
+        Up(clockwise) => (0, clockwise),
+        Down(clockwise) => (1, clockwise),
+        Front(clockwise) => (2, clockwise),
+        Back(clockwise) => (3, clockwise),
+        Left(clockwise) => (4, clockwise),
+        Right(clockwise) => (5, clockwise),
+        UpDouble(clockwise) => (6, clockwise),
+        DownDouble(clockwise) => (7, clockwise),
+        FrontDouble(clockwise) => (8, clockwise),
+        BackDouble(clockwise) => (9, clockwise),
+        LeftDouble(clockwise) => (10, clockwise),
+        RightDouble(clockwise) => (11, clockwise),
+        Middle(clockwise) => (12, clockwise),
+        Equator(clockwise) => (13, clockwise),
+        Standing(clockwise) => (14, clockwise),
+        X(clockwise) => (15, clockwise),
+        Y(clockwise) => (16, clockwise),
+        Z(clockwise) => (17, clockwise),
+        Compound(_, _, _) => unreachable!(),
+    };
+    k * 2 + if clockwise { 0 } else { 1 }
+}
+
+/// All the basic operations
+fn basic_ops() -> Vec<Operation> {
+    use Operation::*;
+    let mut ops = vec![];
+    for clockwise in [true, false] {
+        ops.extend([
+            Up(clockwise),
+            Down(clockwise),
+            Front(clockwise),
+            Back(clockwise),
+            Left(clockwise),
+            Right(clockwise),
+            UpDouble(clockwise),
+            DownDouble(clockwise),
+            FrontDouble(clockwise),
+            BackDouble(clockwise),
+            LeftDouble(clockwise),
+            RightDouble(clockwise),
+            Middle(clockwise),
+            Equator(clockwise),
+            Standing(clockwise),
+            X(clockwise),
+            Y(clockwise),
+            Z(clockwise),
+        ]);
+    }
+    ops
+}
+
+/// Permutations of the basic operations, computed once
+fn basic_table() -> &'static [Permutation; NUM_BASIC] {
+    static TABLE: OnceLock<[Permutation; NUM_BASIC]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let mut table = [Permutation::identity(); NUM_BASIC];
+        for op in basic_ops() {
+            table[basic_index(&op)] = Permutation::by_cycles(&op);
+        }
+        table
+    })
+}
+
+#[cfg(test)]
+mod test_permutation {
+    use crate::entities::*;
+    use crate::read;
+
+    #[test]
+    fn test_same_as_cycles() {
+        let (cube, _) = read::read("Scramble { R U F' L D B2 }");
+        let ops = read::parse_ops("U D' F B' L R' u d' f b' l r' M E S x y' z")
+            .unwrap()
+            .1;
+        assert_eq!(ops.len(), 18);
+        for op in ops.data.iter() {
+            let mut c = cube.clone();
+            c.apply(op);
+            let mut d = cube.clone();
+            d.apply_by_cycles(op);
+            assert_eq!(c, d);
+        }
+    }
+
+    #[test]
+    fn test_compound() {
+        let (cube, _) = read::read("Scramble { R U F' L D B2 }");
+        let e = read::parse_ops("R B' R' F R B R' F' R B R' F R B' R' F'")
+            .unwrap()
+            .1;
+        for clockwise in [true, false] {
+            let op = Operation::Compound("E".to_string(), clockwise, e.data.clone());
+            let mut c = cube.clone();
+            c.apply(&op);
+            let mut d = cube.clone();
+            d.apply_by_cycles(&op);
+            assert_eq!(c, d);
+        }
+        let perm = Permutation::of(&Operation::Compound("E".to_string(), true, e.data.clone()));
+        assert_eq!(perm.then(&perm.inverse()), Permutation::identity());
+    }
+}
@@ -0,0 +1,272 @@
+use crate::entities::{Cube, FaceIndex, Ops, Permutation};
+use crate::util;
+
+/// A corner or an edge piece, by its home position
+/// (the index in `util::corners()` or `util::edges()`).
+/// Also used as the name of a position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Piece {
+    Corner(usize),
+    Edge(usize),
+}
+
+/// Unit normal of the face
+fn normal(face: FaceIndex) -> [i32; 3] {
+    use FaceIndex::*;
+    match face {
+        Right => [1, 0, 0],
+        Left => [-1, 0, 0],
+        Up => [0, 1, 0],
+        Down => [0, -1, 0],
+        Front => [0, 0, 1],
+        Back => [0, 0, -1],
+    }
+}
+
+fn letter(face: FaceIndex) -> char {
+    use FaceIndex::*;
+    match face {
+        Front => 'F',
+        Back => 'B',
+        Up => 'U',
+        Down => 'D',
+        Left => 'L',
+        Right => 'R',
+    }
+}
+
+/// U/D first, and then F/B
+fn is_reference(face: FaceIndex, edge: bool) -> bool {
+    use FaceIndex::*;
+    match face {
+        Up | Down => true,
+        Front | Back => edge,
+        _ => false,
+    }
+}
+
+impl Piece {
+    pub fn all() -> Vec<Piece> {
+        let corners = (0..util::corners().len()).map(Piece::Corner);
+        let edges = (0..util::edges().len()).map(Piece::Edge);
+        corners.chain(edges).collect()
+    }
+
+    pub fn is_corner(&self) -> bool {
+        matches!(self, Piece::Corner(_))
+    }
+
+    /// Num of the orientations (3 for corners, 2 for edges)
+    pub fn orientations(&self) -> usize {
+        if self.is_corner() {
+            3
+        } else {
+            2
+        }
+    }
+
+    /// Facelets of the position, from the reference one (U/D, or F/B for the middle edges).
+    /// The corners go clockwise.
+    pub fn facelets(&self) -> Vec<(FaceIndex, usize, usize)> {
+        let mut facelets = match *self {
+            Piece::Corner(k) => {
+                let (a, b, c) = util::corners()[k];
+                let [na, nb, nc] = [normal(a.0), normal(b.0), normal(c.0)];
+                let cross = [
+                    nb[1] * nc[2] - nb[2] * nc[1],
+                    nb[2] * nc[0] - nb[0] * nc[2],
+                    nb[0] * nc[1] - nb[1] * nc[0],
+                ];
+                let det: i32 = (0..3).map(|i| na[i] * cross[i]).sum();
+                // Clockwise seen from the outside if det < 0
+                if det < 0 {
+                    vec![a, b, c]
+                } else {
+                    vec![a, c, b]
+                }
+            }
+            Piece::Edge(k) => {
+                let (a, b) = util::edges()[k];
+                vec![a, b]
+            }
+        };
+        let edge = !self.is_corner();
+        let i = facelets
+            .iter()
+            .position(|f| is_reference(f.0, edge))
+            .unwrap();
+        facelets.rotate_left(i);
+        facelets
+    }
+
+    /// Sticker indices of the facelets
+    pub fn stickers(&self) -> Vec<usize> {
+        self.facelets()
+            .into_iter()
+            .map(|(face, i, j)| Cube::sticker(face, i, j))
+            .collect()
+    }
+
+    /// The piece having the sticker, and the index of the facelet. None for the centers.
+    pub fn of_sticker(sticker: usize) -> Option<(Piece, usize)> {
+        Piece::all().into_iter().find_map(|piece| {
+            piece
+                .stickers()
+                .iter()
+                .position(|&k| k == sticker)
+                .map(|i| (piece, i))
+        })
+    }
+
+    /// The sticker named from its face (e.g. RUF: the facelet on R of the corner URF)
+    pub fn sticker_named(name: &str) -> Result<usize, String> {
+        let piece: Piece = name.parse()?;
+        let face = name.to_uppercase().chars().next();
+        piece
+            .facelets()
+            .into_iter()
+            .find(|f| Some(letter(f.0)) == face)
+            .map(|(face, i, j)| Cube::sticker(face, i, j))
+            .ok_or(format!("Invalid sticker: {}", name))
+    }
+
+    /// e.g. URF, UF
+    pub fn name(&self) -> String {
+        self.facelets().into_iter().map(|f| letter(f.0)).collect()
+    }
+
+    /// The piece at the position after the permutation, and its orientation
+    /// (the index of its facelet on the reference facelet of the position)
+    pub fn at(position: Piece, perm: &Permutation) -> (Piece, usize) {
+        let source = perm.source(position.stickers()[0]);
+        for piece in Piece::all() {
+            if piece.is_corner() != position.is_corner() {
+                continue;
+            }
+            if let Some(o) = piece.stickers().iter().position(|&k| k == source) {
+                return (piece, o);
+            }
+        }
+        unreachable!()
+    }
+
+    /// Where the piece is after the permutation, and its orientation
+    pub fn locate(&self, perm: &Permutation) -> (Piece, usize) {
+        Piece::all()
+            .into_iter()
+            .map(|position| (position, Piece::at(position, perm)))
+            .find(|(_, (piece, _))| piece == self)
+            .map(|(position, (_, o))| (position, o))
+            .unwrap()
+    }
+
+    /// Name of the position from the facelet of the reference sticker of the piece
+    /// with the orientation `o` (e.g. RFU: the piece shows its U/D color on R)
+    pub fn oriented_name(&self, o: usize) -> String {
+        let mut name: Vec<char> = self.name().chars().collect();
+        let n = name.len();
+        name.rotate_left((n - o % n) % n);
+        name.into_iter().collect()
+    }
+}
+
+impl Ops {
+    /// Where the piece is after each op (the first is before the ops),
+    /// as the position and the orientation
+    pub fn trace(&self, piece: Piece) -> Vec<(Piece, usize)> {
+        let mut perm = Permutation::identity();
+        let mut trace = vec![piece.locate(&perm)];
+        for op in self.data.iter() {
+            perm = perm.then(&Permutation::of(op));
+            trace.push(piece.locate(&perm));
+        }
+        trace
+    }
+
+    /// The piece which ends up at the position, and its orientation
+    pub fn piece_at(&self, position: Piece) -> (Piece, usize) {
+        Piece::at(position, &Permutation::of_ops(self))
+    }
+}
+
+impl std::fmt::Display for Piece {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl std::str::FromStr for Piece {
+    type Err = String;
+    /// The faces in any order (e.g. UFR, URF, FU)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut letters: Vec<char> = s.to_uppercase().chars().collect();
+        letters.sort();
+        Piece::all()
+            .into_iter()
+            .find(|piece| {
+                let mut name: Vec<char> = piece.name().chars().collect();
+                name.sort();
+                name == letters
+            })
+            .ok_or(format!("Invalid piece: {}", s))
+    }
+}
+
+#[cfg(test)]
+mod test_piece {
+    use crate::entities::*;
+    use crate::read;
+    use Operation::*;
+
+    #[test]
+    fn test_piece() {
+        let names: Vec<String> = Piece::all().iter().map(|p| p.name()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "UFL", "URF", "ULB", "UBR", "DLF", "DFR", "DRB", "DBL", "UF", "UR", "UB", "UL",
+                "FL", "FR", "BR", "BL", "DF", "DL", "DR", "DB"
+            ]
+        );
+        assert_eq!("UFR".parse::<Piece>(), Ok(Piece::Corner(1)));
+        assert_eq!("fu".parse::<Piece>(), Ok(Piece::Edge(0)));
+        assert!("UD".parse::<Piece>().is_err());
+
+        // R moves URF to UBR, with the F sticker up
+        let perm = Permutation::of(&Right(true));
+        let urf: Piece = "URF".parse().unwrap();
+        let (position, o) = urf.locate(&perm);
+        assert_eq!(position.name(), "UBR");
+        assert_eq!(o, 2);
+        assert_eq!(Piece::at(position, &perm), (urf, o));
+        assert_eq!(position.oriented_name(o), "BRU");
+
+        let ops = read::parse_ops("R U R' U'").unwrap().1;
+        let trace: Vec<String> = ops
+            .trace(urf)
+            .iter()
+            .map(|(position, o)| position.oriented_name(*o))
+            .collect();
+        assert_eq!(trace, vec!["URF", "BRU", "RFU", "RDF", "RDF"]);
+        let df: Piece = "DF".parse().unwrap();
+        assert_eq!(ops.piece_at(df), (df, 0));
+        let (piece, o) = ops.piece_at("UR".parse().unwrap());
+        assert_eq!(ops.trace(piece).last(), Some(&("UR".parse().unwrap(), o)));
+
+        assert_eq!(
+            Piece::sticker_named("RUF"),
+            Ok(Cube::sticker(FaceIndex::Right, 0, 0))
+        );
+        assert_eq!(
+            Piece::sticker_named("fu"),
+            Ok(Cube::sticker(FaceIndex::Front, 0, 1))
+        );
+        assert!(Piece::sticker_named("RL").is_err());
+
+        // U keeps the orientations
+        let perm = Permutation::of(&Up(true));
+        for piece in Piece::all() {
+            assert_eq!(piece.locate(&perm).1, 0);
+        }
+    }
+}
@@ -0,0 +1,431 @@
+use crate::entities::{Operation, Ops, Permutation};
+use crate::util;
+
+/// Options of `Ops::simplify`
+#[derive(Debug, Clone, Default)]
+pub struct SimplifyOptions {
+    /// Fold the rotations away by relabelling the following moves
+    /// (the net rotation is left at the end)
+    pub rotations: bool,
+    /// Merge the parallel moves into a slice or wide move (e.g. R L' x' -> M, R M' -> r)
+    pub slices: bool,
+}
+
+/// Fewest xyz turns of the rotation (e.g. "y", "xx")
+fn rotation_name(rotation: &Permutation) -> String {
+    use Operation::*;
+    let mut best: Option<Ops> = None;
+    for a in 0..4 {
+        for b in 0..4 {
+            for c in 0..4 {
+                let ops = Ops::new([vec![X(true); a], vec![Y(true); b], vec![Z(true); c]].concat())
+                    .shorten();
+                if Permutation::of_ops(&ops) == *rotation
+                    && best.as_ref().is_none_or(|best| ops.len() < best.len())
+                {
+                    best = Some(ops);
+                }
+            }
+        }
+    }
+    best.map(|ops| ops.to_string()).unwrap_or_default()
+}
+
+impl Operation {
+    /// The operation which does the same before `rotation` as `self` after it:
+    /// `rotation self` == `self.relabel(rotation) rotation`
+    pub fn relabel(&self, rotation: &Permutation) -> Operation {
+        match self {
+            Operation::Compound(name, clockwise, operations) => Operation::Compound(
+                if *rotation == Permutation::identity() {
+                    name.clone()
+                } else {
+                    format!("{}:{}", rotation_name(rotation), name)
+                },
+                *clockwise,
+                operations.iter().map(|op| op.relabel(rotation)).collect(),
+            ),
+            _ => rotation
+                .inverse()
+                .conjugate(&Permutation::of(self))
+                .basic()
+                .unwrap(),
+        }
+    }
+}
+
+impl Ops {
+    /// Cancel the redundant moves, also through the commuting moves (e.g. R L R' -> L, R R2 -> R').
+    /// The compounds are kept unless their moves cancel with the others (or hold rotations
+    /// to fold away), and the half turns are written clockwise (e.g. U' U' -> U U).
+    pub fn simplify(&self, options: &SimplifyOptions) -> Self {
+        let ops = if options.rotations {
+            self.expand_rotations()
+        } else {
+            self.clone()
+        };
+        let mut ops = ops.shorten().expand_cancelling();
+        if options.slices {
+            ops = ops.merge_all();
+        }
+        if options.rotations {
+            ops = ops.remove_rotations().shorten().expand_cancelling();
+            if options.slices {
+                ops = ops.merge_all();
+            }
+        }
+        ops.half_turns()
+    }
+
+    /// Expand the compounds with a rotation inside
+    fn expand_rotations(&self) -> Self {
+        let mut ops = Ops::default();
+        for op in self.data.iter() {
+            let single = Ops::new(vec![op.clone()]);
+            if op.is_compound() && single.expand().data.iter().any(|op| op.is_rotation()) {
+                ops.extend(&single.expand());
+            } else {
+                ops.push(op.clone());
+            }
+        }
+        ops
+    }
+
+    /// Expand the compounds whose moves cancel with the others
+    fn expand_cancelling(&self) -> Self {
+        let mut ops = self.clone();
+        'expand: loop {
+            for (k, op) in ops.data.iter().enumerate() {
+                if !op.is_compound() {
+                    continue;
+                }
+                let mut expanded = Ops::new(ops.data[..k].to_vec());
+                expanded.extend(&Ops::new(vec![op.clone()]).expand());
+                expanded.extend(&Ops::new(ops.data[k + 1..].to_vec()));
+                let shortened = expanded.shorten();
+                if shortened.expand().len() < expanded.expand().len() {
+                    ops = shortened;
+                    continue 'expand;
+                }
+            }
+            return ops;
+        }
+    }
+
+    /// Write the half turns clockwise (e.g. U' U' -> U U)
+    fn half_turns(&self) -> Self {
+        let mut ops = self.clone();
+        for i in 1..ops.len() {
+            let op = ops.data[i].clone();
+            if !op.is_compound() && op.is_reversed() && ops.data[i - 1] == op {
+                ops.data[i - 1] = op.rev();
+                ops.data[i] = op.rev();
+            }
+        }
+        ops
+    }
+
+    fn merge_all(&self) -> Self {
+        let mut ops = self.clone();
+        while let Some(merged) = ops.merge_parallel() {
+            ops = merged.shorten();
+        }
+        ops
+    }
+
+    /// Push the rotations through to the end, relabelling the moves after them
+    /// (e.g. z U R z' -> L U). The net rotation, if any, is left at the end.
+    pub fn remove_rotations(&self) -> Self {
+        let mut rotation = Permutation::identity();
+        let mut ops = Ops::default();
+        for op in self.data.iter() {
+            if op.is_rotation() {
+                rotation = rotation.then(&Permutation::of(op));
+            } else {
+                ops.push(op.relabel(&rotation));
+            }
+        }
+        ops.extend(&shortest_rotation(&rotation));
+        ops
+    }
+
+    /// Insert the rotations which make the moves easier to turn (e.g. B U B' -> y R U R').
+    /// The moves after the rotations are relabelled, so the cube ends rotated:
+    /// the result is `self` and then a net rotation.
+    pub fn add_rotations(&self) -> Self {
+        let rotations: Vec<Permutation> =
+            util::rotations().iter().map(Permutation::of_ops).collect();
+        let n = rotations.len();
+        // Cost of the rotation from `a` to `b`
+        let switch: Vec<Vec<usize>> = rotations
+            .iter()
+            .map(|a| {
+                rotations
+                    .iter()
+                    .map(|b| shortest_rotation(&a.inverse().then(b)).len() * ROTATION_COST)
+                    .collect()
+            })
+            .collect();
+        let moves = self.remove_rotations();
+        let moves: Vec<&Operation> = moves.data.iter().filter(|op| !op.is_rotation()).collect();
+
+        // costs[i][r]: min cost of the first i moves ending in the rotation r
+        let mut costs = vec![vec![usize::MAX; n]; moves.len() + 1];
+        let mut from = vec![vec![0; n]; moves.len() + 1];
+        costs[0][0] = 0;
+        for (i, op) in moves.iter().enumerate() {
+            for b in 0..n {
+                let cost = move_cost(&op.relabel(&rotations[b].inverse()));
+                for a in 0..n {
+                    if costs[i][a] == usize::MAX {
+                        continue;
+                    }
+                    let c = costs[i][a] + switch[a][b] + cost;
+                    if c < costs[i + 1][b] {
+                        costs[i + 1][b] = c;
+                        from[i + 1][b] = a;
+                    }
+                }
+            }
+        }
+        let mut path = vec![0; moves.len() + 1];
+        path[moves.len()] = (0..n).min_by_key(|&r| costs[moves.len()][r]).unwrap_or(0);
+        for i in (0..moves.len()).rev() {
+            path[i] = from[i + 1][path[i + 1]];
+        }
+
+        let mut ops = Ops::default();
+        for (i, op) in moves.iter().enumerate() {
+            let (a, b) = (path[i], path[i + 1]);
+            if a != b {
+                ops.extend(&shortest_rotation(
+                    &rotations[a].inverse().then(&rotations[b]),
+                ));
+            }
+            ops.push(op.relabel(&rotations[b].inverse()));
+        }
+        ops
+    }
+
+    /// Replace 2 or 3 moves on the same axis by one move which is not a rotation.
+    /// None if nothing to merge.
+    fn merge_parallel(&self) -> Option<Self> {
+        let n = self.len();
+        let mut i = 0;
+        while i < n {
+            let axis = self.data[i].axis();
+            let mut j = i + 1;
+            while j < n && axis.is_some() && self.data[j].axis() == axis {
+                j += 1;
+            }
+            for subset in subsets(i, j) {
+                let perm = subset.iter().fold(Permutation::identity(), |perm, &k| {
+                    perm.then(&Permutation::of(&self.data[k]))
+                });
+                match perm.basic() {
+                    Some(op) if !op.is_rotation() => {
+                        let mut data = vec![];
+                        for (k, o) in self.data.iter().enumerate() {
+                            if k == subset[0] {
+                                data.push(op.clone());
+                            } else if !subset.contains(&k) {
+                                data.push(o.clone());
+                            }
+                        }
+                        return Some(Ops::new(data));
+                    }
+                    _ => {}
+                }
+            }
+            i = j;
+        }
+        None
+    }
+}
+
+/// Cost of a rotation in `add_rotations`
+const ROTATION_COST: usize = 20;
+
+/// Rough cost to turn the move (R and U are the easiest)
+fn move_cost(op: &Operation) -> usize {
+    use Operation::*;
+    match op {
+        Up(_) | Right(_) => 10,
+        Left(_) | Front(_) | Down(_) | RightDouble(_) | LeftDouble(_) | Middle(_) => 14,
+        UpDouble(_) => 16,
+        Back(_) | DownDouble(_) | FrontDouble(_) | BackDouble(_) => 25,
+        Equator(_) | Standing(_) => 30,
+        X(_) | Y(_) | Z(_) => ROTATION_COST,
+        Compound(_, _, operations) => operations.iter().map(move_cost).sum(),
+    }
+}
+
+/// Subsets of 2 or 3 indices in `i..j`
+fn subsets(i: usize, j: usize) -> Vec<Vec<usize>> {
+    let mut subsets = vec![];
+    for a in i..j {
+        for b in a + 1..j {
+            subsets.push(vec![a, b]);
+            for c in b + 1..j {
+                subsets.push(vec![a, b, c]);
+            }
+        }
+    }
+    subsets
+}
+
+/// The shortest rotations (x, y, z) to the permutation
+fn shortest_rotation(perm: &Permutation) -> Ops {
+    use Operation::*;
+    let mut layer = vec![(Ops::default(), Permutation::identity())];
+    loop {
+        if let Some((ops, _)) = layer.iter().find(|(_, p)| p == perm) {
+            return ops.clone();
+        }
+        let mut next = vec![];
+        for (ops, p) in layer.iter() {
+            for op in [X(true), X(false), Y(true), Y(false), Z(true), Z(false)] {
+                let mut ops = ops.clone();
+                let p = p.then(&Permutation::of(&op));
+                ops.push(op);
+                next.push((ops, p));
+            }
+        }
+        layer = next;
+    }
+}
+
+#[cfg(test)]
+mod test_simplify {
+    use crate::entities::*;
+    use crate::read;
+    use crate::util;
+
+    fn simplify(ops: &str, rotations: bool, slices: bool) -> String {
+        let ops = read::parse_ops(ops).unwrap().1;
+        let options = SimplifyOptions { rotations, slices };
+        let simplified = ops.simplify(&options);
+        let (cube, _) = read::read("Scramble { R U F' L D B }");
+        assert_eq!(simplified.apply(&cube), ops.apply(&cube));
+        format!("{}", simplified)
+    }
+
+    #[test]
+    fn test_simplify() {
+        assert_eq!(simplify("R R R", false, false), "R'");
+        assert_eq!(simplify("R L R'", false, false), "L");
+        assert_eq!(simplify("U D R R' U'", false, false), "D");
+        assert_eq!(simplify("F R U R' U' F'", false, false), "FRUR'U'F'");
+    }
+
+    #[test]
+    fn test_half_turns() {
+        assert_eq!(simplify("U' U'", false, false), "UU");
+        assert_eq!(simplify("R U' D U'", false, false), "RUUD");
+        assert_eq!(simplify("M' U M' M'", false, true), "M'UMM");
+    }
+
+    #[test]
+    fn test_solver_outputs() {
+        // No half turns written counterclockwise (e.g. U' U') are left by the solvers
+        let scrambles = [
+            "Scramble { R' D2 F2 B' R D' F2 B' L R2 B2 D B2 D R2 U2 R2 F2 U2 R2 F }",
+            "Scramble { F L2 F2 L2 B' D2 B U2 L2 U2 L2 U2 R D' L B2 D' F' D' B2 L }",
+        ];
+        for scramble in scrambles {
+            let (cube, goal) = read::read(scramble);
+            let algs = [
+                crate::solver::cfop(&cube, false).unwrap(),
+                crate::solver::roux(&cube, false).unwrap(),
+            ];
+            for alg in algs {
+                let c = alg.apply(&cube);
+                assert!(util::rotations().iter().any(|r| r.apply(&c) == goal));
+                assert!(alg
+                    .data
+                    .windows(2)
+                    .all(|w| w[0] != w[1] || !w[0].is_reversed()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_compounds() {
+        let sexy = Operation::Compound(
+            "Sx".to_string(),
+            true,
+            read::parse_ops("R U R' U'").unwrap().1.data,
+        );
+        let (cube, _) = read::read("Scramble { R U F' L D B }");
+        let check = |ops: Ops, expected: &str| {
+            let simplified = ops.simplify(&SimplifyOptions::default());
+            assert_eq!(format!("{}", simplified), expected);
+            assert_eq!(simplified.apply(&cube), ops.apply(&cube));
+        };
+        // Kept unless their moves cancel
+        let mut ops = read::parse_ops("F").unwrap().1;
+        ops.extend(&Ops::new(vec![sexy.clone(), sexy.clone()]));
+        ops.extend(&read::parse_ops("F'").unwrap().1);
+        check(ops, "F(Sx)(Sx)F'");
+        check(Ops::new(vec![sexy.clone(), sexy.rev()]), "(nop)");
+        let mut ops = Ops::new(vec![sexy.clone()]);
+        ops.extend(&read::parse_ops("U R").unwrap().1);
+        check(ops, "RU");
+        let mut ops = read::parse_ops("U'").unwrap().1;
+        ops.push(sexy.rev());
+        check(ops, "RU'R'");
+    }
+
+    #[test]
+    fn test_rotations() {
+        assert_eq!(simplify("y R", true, false), "By");
+        assert_eq!(simplify("x R U x'", true, false), "RF");
+        assert_eq!(simplify("y R y' B'", true, false), "(nop)");
+        assert_eq!(simplify("x y x' y'", true, false), "xz");
+    }
+
+    #[test]
+    fn test_remove_rotations() {
+        let ops = read::parse_ops("z U R' D R R U' R D' U R' D R R U' R D' z'")
+            .unwrap()
+            .1;
+        let removed = ops.remove_rotations();
+        assert!(removed.data.iter().all(|op| !op.is_rotation()));
+        assert_eq!(format!("{}", removed), "LU'RUUL'UR'LU'RUUL'UR'");
+        let (cube, _) = read::read("Scramble { R U F' L D B }");
+        assert_eq!(removed.apply(&cube), ops.apply(&cube));
+
+        // Relabelled compound, named after the rotation
+        let sexy = Operation::Compound(
+            "Sx".to_string(),
+            true,
+            read::parse_ops("R U R' U'").unwrap().1.data,
+        );
+        let ops = Ops::new(vec![Operation::Y(true), sexy]);
+        let removed = ops.remove_rotations();
+        assert_eq!(format!("{}", removed), "(y:Sx)y");
+        assert_eq!(removed.apply(&cube), ops.apply(&cube));
+    }
+
+    #[test]
+    fn test_add_rotations() {
+        let ops = read::parse_ops("B U B' U'").unwrap().1;
+        let added = ops.add_rotations();
+        assert_eq!(format!("{}", added), "yRUR'U'");
+        // Same as the original and then a rotation
+        let (cube, _) = read::read("Scramble { R U F' L D B }");
+        let c = ops.apply(&cube);
+        let d = added.apply(&cube);
+        assert!(util::rotations().iter().any(|r| r.apply(&c) == d));
+        assert_eq!(format!("{}", added.remove_rotations()), "BUB'U'y");
+    }
+
+    #[test]
+    fn test_slices() {
+        assert_eq!(simplify("R M'", false, true), "r");
+        assert_eq!(simplify("R L' x'", false, true), "M");
+        assert_eq!(simplify("L' R x' U", true, true), "MU");
+        assert_eq!(simplify("R L", false, true), "RL");
+        assert_eq!(simplify("x' L' R U", false, true), "MU");
+    }
+}
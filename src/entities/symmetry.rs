@@ -0,0 +1,189 @@
+use crate::entities::{Color, Cube, Ops, Permutation};
+use crate::util;
+use std::collections::BTreeSet;
+
+/// A symmetry of the cube: the mirror between the left and the right (if `mirror`),
+/// and then the rotation. There are 24 rotations x 2 = 48 symmetries.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symmetry {
+    pub rotation: Ops,
+    pub mirror: bool,
+    perm: Permutation,
+}
+
+impl Symmetry {
+    /// All the 48 symmetries (the identity first)
+    pub fn all() -> Vec<Self> {
+        let mut symmetries = vec![];
+        for mirror in [false, true] {
+            for rotation in util::rotations() {
+                let mut perm = if mirror {
+                    Permutation::mirror()
+                } else {
+                    Permutation::identity()
+                };
+                for op in rotation.data.iter() {
+                    perm = perm.then(&Permutation::of(op));
+                }
+                symmetries.push(Self {
+                    rotation,
+                    mirror,
+                    perm,
+                });
+            }
+        }
+        symmetries
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.perm == Permutation::identity()
+    }
+
+    pub fn permutation(&self) -> &Permutation {
+        &self.perm
+    }
+
+    /// Table to recolor the cube after the permutation,
+    /// so that the centers agree with `reference`.
+    /// The masked centers (`.` or `*`) go to the masked ones, and their colors are not used.
+    /// None if the centers of `reference` are not distinct colors.
+    pub fn colors(&self, reference: &Cube) -> Option<[Color; 8]> {
+        let masked = |col: Color| col == Color::Other || col == Color::Wildcard;
+        let stickers = reference.stickers();
+        let permuted = self.perm.permute(&stickers);
+        let mut colors = Color::ALL;
+        let mut used = BTreeSet::new();
+        for (face, i, j) in util::centers() {
+            let k = Cube::sticker(face, i, j);
+            if masked(stickers[k]) || masked(permuted[k]) {
+                if masked(stickers[k]) != masked(permuted[k]) {
+                    return None;
+                }
+                continue;
+            }
+            if !used.insert(stickers[k]) {
+                return None;
+            }
+            colors[permuted[k] as usize] = stickers[k];
+        }
+        if stickers
+            .iter()
+            .any(|&col| !masked(col) && !used.contains(&col))
+        {
+            return None;
+        }
+        Some(colors)
+    }
+
+    /// Conjugate the cube by the symmetry:
+    /// the cube seen from the symmetric viewpoint, recolored to the centers of `reference`
+    pub fn apply(&self, cube: &Cube, reference: &Cube) -> Option<Cube> {
+        let colors = self.colors(reference)?;
+        let stickers = self.perm.permute(&cube.stickers());
+        let mut cube = cube.clone();
+        cube.set_stickers(&stickers.map(|col| colors[col as usize]));
+        Some(cube)
+    }
+
+    /// Permutation of the operation symmetric to `perm`
+    pub fn conjugate(&self, perm: &Permutation) -> Permutation {
+        self.perm.conjugate(perm)
+    }
+}
+
+impl std::fmt::Display for Symmetry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.mirror {
+            write!(f, "mirror+")?;
+        }
+        write!(f, "{}", self.rotation)
+    }
+}
+
+impl Cube {
+    /// Symmetries which keep the cube (as a goal)
+    pub fn symmetries(&self) -> Vec<Symmetry> {
+        Symmetry::all()
+            .into_iter()
+            .filter(|sym| sym.apply(self, self).as_ref() == Some(self))
+            .collect()
+    }
+
+    /// Distinct cubes equivalent to the cube by the symmetries (sorted).
+    /// The colors are relabeled to the own centers.
+    pub fn symmetry_class(&self) -> Vec<Cube> {
+        let class: BTreeSet<Cube> = Symmetry::all()
+            .iter()
+            .filter_map(|sym| sym.apply(self, self))
+            .collect();
+        class.into_iter().collect()
+    }
+
+    /// The representative (the smallest) of the symmetry class
+    pub fn canonical(&self) -> Cube {
+        self.symmetry_class()
+            .into_iter()
+            .next()
+            .unwrap_or_else(|| self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test_symmetry {
+    use crate::entities::*;
+    use crate::read;
+    use Operation::*;
+
+    #[test]
+    fn test_all() {
+        let symmetries = Symmetry::all();
+        assert_eq!(symmetries.len(), 48);
+        assert!(symmetries[0].is_identity());
+        let perms: Vec<_> = symmetries.iter().map(|sym| *sym.permutation()).collect();
+        for (i, p) in perms.iter().enumerate() {
+            assert!(!perms[..i].contains(p));
+        }
+    }
+
+    #[test]
+    fn test_conjugate() {
+        let mirror = Symmetry::all()
+            .into_iter()
+            .find(|sym| sym.mirror && sym.rotation.is_empty());
+        let mirror = mirror.unwrap();
+        assert_eq!(
+            mirror.conjugate(&Permutation::of(&Right(true))),
+            Permutation::of(&Left(false))
+        );
+        assert_eq!(
+            mirror.conjugate(&Permutation::of(&Up(true))),
+            Permutation::of(&Up(false))
+        );
+    }
+
+    #[test]
+    fn test_symmetry_class() {
+        let (solved, _) = read::read("Scramble { U U' }");
+        assert_eq!(solved.symmetries().len(), 48);
+        assert_eq!(solved.symmetry_class(), vec![solved.clone()]);
+
+        // Quarter turns of the 6 faces, in both directions
+        let (u, _) = read::read("Scramble { U }");
+        assert_eq!(u.symmetry_class().len(), 12);
+        let (r, _) = read::read("Scramble { R' }");
+        assert_eq!(u.canonical(), r.canonical());
+        let (r2, _) = read::read("Scramble { R R }");
+        assert_ne!(u.canonical(), r2.canonical());
+
+        // Symmetries keep the cube after the symmetric move
+        for sym in solved.symmetries() {
+            let d = sym.apply(&u, &solved).unwrap();
+            let mut c = solved.clone();
+            c.set_stickers(
+                &sym.conjugate(&Permutation::of(&Up(true)))
+                    .permute(&c.stickers()),
+            );
+            assert_eq!(c, d);
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use crate::entities::{Operation, Ops, Permutation};
+
+/// The plane of a mirror, by the faces swapped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MirrorAxis {
+    LeftRight,
+    FrontBack,
+    UpDown,
+}
+
+impl MirrorAxis {
+    pub fn permutation(&self) -> Permutation {
+        use Operation::*;
+        let mirror = Permutation::mirror();
+        match self {
+            MirrorAxis::LeftRight => mirror,
+            MirrorAxis::FrontBack => Permutation::of(&Y(true)).conjugate(&mirror),
+            MirrorAxis::UpDown => Permutation::of(&Z(true)).conjugate(&mirror),
+        }
+    }
+}
+
+impl std::fmt::Display for MirrorAxis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            MirrorAxis::LeftRight => "LR",
+            MirrorAxis::FrontBack => "FB",
+            MirrorAxis::UpDown => "UD",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::str::FromStr for MirrorAxis {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "LR" | "RL" => Ok(MirrorAxis::LeftRight),
+            "FB" | "BF" => Ok(MirrorAxis::FrontBack),
+            "UD" | "DU" => Ok(MirrorAxis::UpDown),
+            _ => Err(format!("Invalid mirror: {} (LR, FB or UD)", s)),
+        }
+    }
+}
+
+impl Operation {
+    /// The operation seen in the mirror (e.g. R -> L' by LR)
+    pub fn mirror(&self, axis: MirrorAxis) -> Operation {
+        match self {
+            Operation::Compound(name, clockwise, operations) => Operation::Compound(
+                format!("{}/{}", name, axis),
+                *clockwise,
+                operations.iter().map(|op| op.mirror(axis)).collect(),
+            ),
+            _ => axis
+                .permutation()
+                .conjugate(&Permutation::of(self))
+                .basic()
+                .unwrap(),
+        }
+    }
+}
+
+impl Ops {
+    /// The mirrored algorithm (e.g. the left-hand variant by LR)
+    pub fn mirror(&self, axis: MirrorAxis) -> Self {
+        Ops::new(self.data.iter().map(|op| op.mirror(axis)).collect())
+    }
+
+    /// The same algorithm performed from the angle after `rotation`
+    /// (e.g. R U R' from y is B U B'): `rotation self rotation'` without the rotations
+    pub fn rotate_by(&self, rotation: &Ops) -> Self {
+        let rotation = Permutation::of_ops(rotation);
+        Ops::new(self.data.iter().map(|op| op.relabel(&rotation)).collect())
+    }
+}
+
+#[cfg(test)]
+mod test_transform {
+    use crate::entities::*;
+    use crate::read;
+
+    fn ops(s: &str) -> Ops {
+        read::parse_ops(s).unwrap().1
+    }
+
+    #[test]
+    fn test_mirror() {
+        use MirrorAxis::*;
+        assert_eq!(ops("R U R' U'").mirror(LeftRight), ops("L' U' L U"));
+        assert_eq!(ops("r M' x").mirror(LeftRight), ops("l' M' x"));
+        assert_eq!(ops("F R B'").mirror(FrontBack), ops("B' R' F"));
+        assert_eq!(ops("U R d").mirror(UpDown), ops("D' R' u'"));
+        let alg = ops("R U R' U' R' F R F'");
+        assert_eq!(alg.mirror(LeftRight).mirror(LeftRight), alg);
+
+        // Mirrored cube by the mirrored alg
+        let (cube, _) = read::read("Scramble { R U F' L D B }");
+        for axis in [LeftRight, FrontBack, UpDown] {
+            let perm = axis.permutation();
+            let mirrored = |c: &Cube| {
+                let mut c = c.clone();
+                c.set_stickers(&perm.permute(&c.stickers()));
+                c
+            };
+            assert_eq!(
+                mirrored(&alg.apply(&mirrored(&cube))),
+                alg.mirror(axis).apply(&cube)
+            );
+        }
+        assert!("XY".parse::<MirrorAxis>().is_err());
+
+        // Mirrored compound, named after the mirror
+        let sexy = Operation::Compound("Sx".to_string(), true, ops("R U R' U'").data);
+        let mirrored = Ops::new(vec![sexy.mirror(LeftRight)]);
+        assert_eq!(format!("{}", mirrored), "(Sx/LR)");
+        assert_eq!(mirrored.expand(), ops("L' U' L U"));
+    }
+
+    #[test]
+    fn test_rotate_by() {
+        assert_eq!(ops("R U R'").rotate_by(&ops("y")), ops("B U B'"));
+        assert_eq!(ops("R U R'").rotate_by(&ops("y y")), ops("L U L'"));
+        let alg = ops("r U R' U'");
+        let mut conjugated = ops("y");
+        conjugated.extend(&alg);
+        conjugated.extend(&ops("y'"));
+        let (cube, _) = read::read("Scramble { R U F' L D B }");
+        assert_eq!(
+            alg.rotate_by(&ops("y")).apply(&cube),
+            conjugated.apply(&cube)
+        );
+    }
+}
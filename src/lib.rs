@@ -1,3 +1,4 @@
+use serde_json::json;
 use wasm_bindgen::prelude::*;
 
 pub mod entities;
@@ -14,42 +15,123 @@ pub fn solve(
     solve_by_roux: bool,
     solve_by_cfop: bool,
 ) -> String {
-    let (init, goal) = read::read(input);
+    if !solve_by_roux && !solve_by_cfop {
+        return solve_within(input, allow_ops, max_depth, num, 0);
+    }
+    let (init, _) = read::read(input);
     if solve_by_roux {
         if let Some(alg) = solver::roux(&init, false) {
             format!("{}", alg)
         } else {
             String::from("(failed:no_solutions)")
         }
-    } else if solve_by_cfop {
-        if let Some(alg) = solver::cfop(&init, false) {
-            format!("{}", alg)
-        } else {
-            String::from("(failed:no_solutions)")
-        }
+    } else if let Some(alg) = solver::cfop(&init, false) {
+        format!("{}", alg)
     } else {
-        match read::parse_ops(allow_ops) {
-            Ok((_, allow_ops)) => {
-                let solutions = solver::search(&init, &goal, allow_ops.data, max_depth, num, false);
-                if solutions.is_empty() {
-                    String::from("(failed:no_solutions)")
+        String::from("(failed:no_solutions)")
+    }
+}
+
+/// Search as `solve`, visiting at most `max_nodes` nodes (0 means unlimited).
+/// "(cutoff)" is appended to the solutions when the search is cut off.
+#[wasm_bindgen]
+pub fn solve_within(
+    input: &str,
+    allow_ops: &str,
+    max_depth: usize,
+    num: usize,
+    max_nodes: usize,
+) -> String {
+    let (init, goal) = read::read(input);
+    match read::parse_ops(allow_ops) {
+        Ok((_, allow_ops)) => {
+            // max_nodes=0 means unlimited
+            let mut options = solver::SearchOptions::new(max_depth, num);
+            if max_nodes > 0 {
+                options.budget.max_nodes = Some(max_nodes);
+            }
+            let result = solver::search_with(&init, &goal, allow_ops.data, &options);
+            if result.solutions.is_empty() {
+                if result.cutoff {
+                    String::from("(failed:cutoff)")
                 } else {
-                    solutions
-                        .into_iter()
-                        .map(|ops| format!("{}", ops))
-                        .collect::<Vec<String>>()
-                        .join(";")
+                    String::from("(failed:no_solutions)")
+                }
+            } else {
+                let mut solutions: Vec<String> = result
+                    .solutions
+                    .into_iter()
+                    .map(|ops| format!("{}", ops))
+                    .collect();
+                if result.cutoff {
+                    solutions.push(String::from("(cutoff)"));
                 }
+                solutions.join(";")
+            }
+        }
+        Err(_) => String::from("(failed:invalid_operations)"),
+    }
+}
+
+/// Search step by step, so as not to block the page
+#[wasm_bindgen]
+pub struct Session {
+    searcher: Option<solver::Searcher>,
+}
+
+#[wasm_bindgen]
+impl Session {
+    #[wasm_bindgen(constructor)]
+    pub fn new(input: &str, allow_ops: &str, max_depth: usize, num: usize) -> Session {
+        let (init, goal) = read::read(input);
+        let searcher = match read::parse_ops(allow_ops) {
+            Ok((_, allow_ops)) => Some(solver::Searcher::new(
+                &init,
+                &goal,
+                allow_ops.data,
+                &solver::SearchOptions::new(max_depth, num),
+            )),
+            Err(_) => None,
+        };
+        Session { searcher }
+    }
+
+    /// Visit at most `nodes` nodes.
+    /// Returns JSON of the progress and the solutions found in this step.
+    pub fn step(&mut self, nodes: usize) -> String {
+        let searcher = match self.searcher.as_mut() {
+            Some(searcher) => searcher,
+            None => {
+                return json!({ "ok": false, "error": "invalid_operations", "done": true })
+                    .to_string()
+            }
+        };
+        let mut solutions = vec![];
+        for event in searcher.visit(nodes) {
+            if let solver::SearchEvent::Solution(ops) = event {
+                solutions.push(format!("{}", ops));
             }
-            Err(_) => String::from("(failed:invalid_operations)"),
         }
+        let progress = searcher.progress();
+        json!({
+            "ok": true,
+            "done": searcher.is_done(),
+            "solutions": solutions,
+            "progress": {
+                "depth": progress.depth,
+                "nodes": progress.nodes,
+                "from_start": progress.from_start,
+                "from_goal": progress.from_goal,
+            },
+        })
+        .to_string()
     }
 }
 
 #[cfg(test)]
 mod test_operation {
 
-    use crate::solve;
+    use crate::{solve, solve_within};
 
     #[test]
     fn test_solve_scramble() {
@@ -59,7 +141,7 @@ mod test_operation {
             }
         ";
         let allow_ops = "D U";
-        let solutions = solve(&input, &allow_ops, 3, 1, false, false);
+        let solutions = solve(input, allow_ops, 3, 1, false, false);
         assert_ne!(solutions, String::from(""));
         assert!(!solutions.as_str().starts_with("(failed"));
     }
@@ -71,7 +153,7 @@ mod test_operation {
               U' F B
             }
         ";
-        let solutions = solve(&input, "", 0, 0, true, false);
+        let solutions = solve(input, "", 0, 0, true, false);
         assert_ne!(solutions, String::from(""));
         assert!(!solutions.as_str().starts_with("(failed"));
     }
@@ -83,8 +165,51 @@ mod test_operation {
               U' F B
             }
         ";
-        let solutions = solve(&input, "", 0, 0, false, true);
+        let solutions = solve(input, "", 0, 0, false, true);
         assert_ne!(solutions, String::from(""));
         assert!(!solutions.as_str().starts_with("(failed"));
     }
+
+    #[test]
+    fn test_session() {
+        use crate::Session;
+        let input = "
+            Scramble {
+              DDU'
+            }
+        ";
+        let mut session = Session::new(input, "D U", 3, 1);
+        let mut solutions = vec![];
+        loop {
+            let res: serde_json::Value = serde_json::from_str(&session.step(10)).unwrap();
+            assert_eq!(res["ok"], true);
+            for sol in res["solutions"].as_array().unwrap() {
+                solutions.push(sol.as_str().unwrap().to_string());
+            }
+            if res["done"] == true {
+                break;
+            }
+        }
+        assert_eq!(solutions.len(), 1);
+        let mut session = Session::new(input, "Q", 3, 1);
+        assert!(session.step(10).contains("invalid_operations"));
+
+        // A step stops at the nodes, even between the progress events
+        let mut session = Session::new("Scramble { R U F L B D }", "UU'DD'FF'BB'LL'RR'", 9, 1);
+        for step in 1..=3 {
+            let res: serde_json::Value = serde_json::from_str(&session.step(100)).unwrap();
+            assert_eq!(res["progress"]["nodes"], 100 * step);
+        }
+    }
+
+    #[test]
+    fn test_solve_cutoff() {
+        let input = "
+            Scramble {
+              R U F
+            }
+        ";
+        let solutions = solve_within(input, "D U", 9, 1, 100);
+        assert_eq!(solutions, String::from("(failed:cutoff)"));
+    }
 }
@@ -1,13 +1,12 @@
-mod entities;
-mod read;
-mod solver;
-mod util;
-
-use crate::entities::*;
+use cube::entities::*;
+use cube::read::{
+    cat, parse_colors, parse_niss, parse_ops, read_constraints, read_goals, read_scramble,
+};
+use cube::solver;
 use log::{error, info, warn};
-use read::{cat, read};
 use serde_json::json;
 use std::env;
+use std::time::Duration;
 use structopt::StructOpt;
 use Operation::*;
 
@@ -15,7 +14,12 @@ use Operation::*;
 struct Opt {
     #[structopt(long, default_value = "9")]
     max_depth: usize,
-    #[structopt(short, long, default_value = "1", help = "max num of algorithm")]
+    #[structopt(
+        short,
+        long,
+        default_value = "1",
+        help = "max num of algorithm (the orders of the commuting moves are not counted twice)"
+    )]
     num: usize,
     #[structopt(short, long)]
     verbose: bool,
@@ -73,6 +77,520 @@ struct Opt {
         help = "Complete solve by Roux method, other options are all ignored"
     )]
     roux: bool,
+
+    #[structopt(
+        long,
+        help = "Blindfold solution with Speffz memo: op (Old Pochmann) or m2 (M2 edges)"
+    )]
+    blind: Option<solver::BlindMethod>,
+
+    #[structopt(
+        long,
+        help = "Color neutral CFOP/Roux, trying the orientations whose down face has one of the colors (e.g. WY, default: all)"
+    )]
+    neutral: Option<Option<String>>,
+
+    #[structopt(long, help = "CFOP: solve the cross with the first pair")]
+    xcross: bool,
+
+    #[structopt(
+        long,
+        help = "CFOP: max depth of the X-cross search from each side (default 6)"
+    )]
+    xcross_depth: Option<usize>,
+
+    #[structopt(
+        long,
+        help = "CFOP: solve the easiest slot first, instead of FL, BL, then FR and BR together"
+    )]
+    easiest_first: bool,
+
+    #[structopt(
+        long,
+        help = "CFOP: also try to solve two pairs at once (the easiest first)"
+    )]
+    multislot: bool,
+
+    #[structopt(
+        long,
+        parse(try_from_str = parse_duration),
+        help = "Time limit of search (e.g. 2s, 500ms, 1m)"
+    )]
+    timeout: Option<Duration>,
+
+    #[structopt(long, help = "Max num of nodes to visit in search")]
+    max_nodes: Option<usize>,
+
+    #[structopt(long, help = "Print solutions and progress of search as JSON lines")]
+    stream: bool,
+
+    #[structopt(
+        long,
+        default_value = "1",
+        help = "Num of threads (0 for all cores); --stream is single-threaded"
+    )]
+    threads: usize,
+
+    #[structopt(
+        long,
+        help = "Reduce the search by the symmetries of the goal (masked with '.', not '*')"
+    )]
+    symmetry: bool,
+
+    #[structopt(
+        long,
+        help = "Rank by the cost of the finger tricks: \"default\" or the costs to change (e.g. \"regrip=20,rotation=10\")"
+    )]
+    ergonomics: Option<Ergonomics>,
+
+    #[structopt(
+        long,
+        help = "With --ergonomics, rank the solutions up to this many moves longer than the shortest (default 2)"
+    )]
+    slack: Option<usize>,
+
+    #[structopt(
+        long,
+        help = "Enumerate all the algorithms up to --max-depth moves (a half turn is two) with each move set, deduped by the effects"
+    )]
+    generate: bool,
+
+    #[structopt(
+        long,
+        number_of_values = 1,
+        help = "Generate: a move set (e.g. \"R U F\"; repeatable, with the one of the flags)"
+    )]
+    moves: Vec<String>,
+
+    #[structopt(long, help = "Generate: max length of the algorithms in the metric")]
+    max_length: Option<usize>,
+
+    #[structopt(
+        long,
+        default_value = "htm",
+        help = "Generate: sort by the length in htm, qtm, stm or etm"
+    )]
+    metric: Metric,
+
+    #[structopt(long, help = "Generate: dedupe the algorithms modulo AUF")]
+    modulo_auf: bool,
+
+    #[structopt(
+        long,
+        help = "Generate: write the algorithms to the file, one per line"
+    )]
+    export: Option<std::path::PathBuf>,
+
+    #[structopt(
+        long,
+        help = "Search on the inverse of the Scramble (the solution is reported inverted back)"
+    )]
+    inverse: bool,
+
+    #[structopt(
+        long,
+        help = "Continue from a NISS skeleton on the Scramble (e.g. \"R U (F' D)\": the moves in parentheses are on the inverse)"
+    )]
+    niss: Option<String>,
+
+    #[structopt(long, help = "Allow any AUF before the solution (reported as pre_auf)")]
+    pre_auf: bool,
+
+    #[structopt(
+        long,
+        help = "Solve the goal modulo AUF (the AUF after the solution is reported as post_auf)"
+    )]
+    post_auf: bool,
+
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(StructOpt)]
+enum Command {
+    /// Transform an algorithm (applied in the order: mirror, from-angle, inverse)
+    Transform {
+        algorithm: String,
+        #[structopt(long, help = "Mirror by the plane between the faces: LR, FB or UD")]
+        mirror: Option<MirrorAxis>,
+        #[structopt(long, help = "Perform from the angle after the rotation (e.g. y, x2)")]
+        from_angle: Option<String>,
+        #[structopt(long, help = "Invert the algorithm")]
+        inverse: bool,
+    },
+    /// Check whether two algorithms have the same effect
+    /// (identical, or modulo rotation, AUF, or only on the last layer)
+    Equiv {
+        algorithm: String,
+        other: String,
+        #[structopt(long, help = "Only identical ones")]
+        exact: bool,
+    },
+    /// Trace the pieces through each move of an algorithm
+    Trace {
+        algorithm: String,
+        #[structopt(
+            long,
+            number_of_values = 1,
+            help = "Piece to trace (e.g. UFR; repeatable, default: all)"
+        )]
+        piece: Vec<Piece>,
+        #[structopt(
+            long,
+            number_of_values = 1,
+            help = "Position to ask which piece ends up there (e.g. DF; repeatable)"
+        )]
+        at: Vec<Piece>,
+    },
+    /// Find 3-style commutators [A: [B, C]] of the 3-cycle of the stickers
+    /// (named from the face, e.g. UFR, RDF)
+    Comm {
+        buffer: String,
+        first: String,
+        second: String,
+        #[structopt(long, help = "Moves to use (e.g. \"U D R M\", default: all the faces)")]
+        moves: Option<String>,
+        #[structopt(long, default_value = "10", help = "Max length of [B, C]")]
+        max_length: usize,
+        #[structopt(long, default_value = "1", help = "Max num of the turns of A")]
+        setup_depth: usize,
+        #[structopt(short, long, default_value = "10", help = "Max num of commutators")]
+        num: usize,
+        #[structopt(long, default_value = "1", help = "Num of threads (0 for all cores)")]
+        threads: usize,
+    },
+    /// Find insertions into a skeleton which solve the scramble, with the most cancellation
+    /// (commutators for a leftover 3-cycle, and the known algorithms)
+    Insert {
+        scramble: String,
+        skeleton: String,
+        #[structopt(
+            long,
+            number_of_values = 1,
+            help = "Known algorithm to insert (repeatable)"
+        )]
+        alg: Vec<String>,
+        #[structopt(
+            long,
+            help = "Moves of the commutators (e.g. \"U D R\", default: all the faces)"
+        )]
+        moves: Option<String>,
+        #[structopt(long, default_value = "10", help = "Max length of [B, C]")]
+        max_length: usize,
+        #[structopt(long, default_value = "0", help = "Max num of the turns of A")]
+        setup_depth: usize,
+        #[structopt(short, long, default_value = "10", help = "Max num of insertions")]
+        num: usize,
+        #[structopt(long, default_value = "1", help = "Num of threads (0 for all cores)")]
+        threads: usize,
+    },
+}
+
+/// Parse the whole string as ops (an empty one is no moves, e.g. for an empty skeleton)
+fn parse_alg(s: &str) -> Option<Ops> {
+    if s.trim().is_empty() {
+        return Some(Ops::default());
+    }
+    match parse_ops(s) {
+        Ok((rest, ops)) if rest.trim().is_empty() => Some(ops),
+        _ => None,
+    }
+}
+
+/// Both directions of the moves (None if invalid, empty for the default)
+fn parse_moves(moves: &Option<String>) -> Option<Vec<Operation>> {
+    match moves {
+        None => Some(vec![]),
+        Some(moves) => parse_alg(moves).map(|ops| {
+            ops.data
+                .iter()
+                .flat_map(|op| [op.clone(), op.rev()])
+                .collect()
+        }),
+    }
+}
+
+/// 0 for all the cores
+fn num_threads(threads: usize) -> usize {
+    if threads > 0 {
+        threads
+    } else {
+        std::thread::available_parallelism().map_or(1, |n| n.get())
+    }
+}
+
+fn run_command(command: &Command) {
+    match command {
+        Command::Transform {
+            algorithm,
+            mirror,
+            from_angle,
+            inverse,
+        } => {
+            let Some(mut alg) = parse_alg(algorithm) else {
+                error!("Invalid algorithm: {}", algorithm);
+                println!("{}", json!({ "ok": false }));
+                return;
+            };
+            if let Some(axis) = mirror {
+                alg = alg.mirror(*axis);
+            }
+            if let Some(rotation) = from_angle {
+                match parse_alg(rotation) {
+                    Some(rotation) if rotation.data.iter().all(|op| op.is_rotation()) => {
+                        alg = alg.rotate_by(&rotation);
+                    }
+                    _ => {
+                        error!("Invalid rotation: {}", rotation);
+                        println!("{}", json!({ "ok": false }));
+                        return;
+                    }
+                }
+            }
+            if *inverse {
+                alg = alg.rev();
+            }
+            println!(
+                "{}",
+                json!({
+                    "ok": true,
+                    "algorithm": format!("{}", alg),
+                    "length": alg.len(),
+                })
+            );
+        }
+        Command::Equiv {
+            algorithm,
+            other,
+            exact,
+        } => {
+            let (Some(a), Some(b)) = (parse_alg(algorithm), parse_alg(other)) else {
+                error!("Invalid algorithm: {} / {}", algorithm, other);
+                println!("{}", json!({ "ok": false }));
+                return;
+            };
+            let options = if *exact {
+                EquivalenceOptions::default()
+            } else {
+                EquivalenceOptions::all()
+            };
+            let equivalence = a.equivalent(&b, &options);
+            println!(
+                "{}",
+                json!({
+                    "ok": true,
+                    "equivalent": equivalence.is_some(),
+                    "relation": equivalence.map(|e| e.to_string()),
+                })
+            );
+        }
+        Command::Trace {
+            algorithm,
+            piece,
+            at,
+        } => {
+            let Some(alg) = parse_alg(algorithm) else {
+                error!("Invalid algorithm: {}", algorithm);
+                println!("{}", json!({ "ok": false }));
+                return;
+            };
+            let pieces = if piece.is_empty() {
+                Piece::all()
+            } else {
+                piece.clone()
+            };
+            // Each column is a piece, and each row is the positions after the move
+            let traces: Vec<Vec<String>> = pieces
+                .iter()
+                .map(|&p| {
+                    alg.trace(p)
+                        .into_iter()
+                        .map(|(position, o)| position.oriented_name(o))
+                        .collect()
+                })
+                .collect();
+            let names: Vec<String> = pieces.iter().map(|p| p.name()).collect();
+            info!(
+                "      {}",
+                names.iter().map(|n| format!("{:4}", n)).collect::<String>()
+            );
+            let mut steps = vec![];
+            for i in 0..=alg.len() {
+                let op = if i == 0 {
+                    String::new()
+                } else {
+                    format!("{}", alg.data[i - 1])
+                };
+                let positions: Vec<&String> = traces.iter().map(|t| &t[i]).collect();
+                info!(
+                    "{:6}{}",
+                    op,
+                    positions
+                        .iter()
+                        .map(|n| format!("{:4}", n))
+                        .collect::<String>()
+                );
+                steps.push(json!({ "move": op, "positions": positions }));
+            }
+            let at: Vec<_> = at
+                .iter()
+                .map(|&position| {
+                    let (piece, o) = alg.piece_at(position);
+                    json!({
+                        "position": position.name(),
+                        "piece": piece.name(),
+                        "orientation": o,
+                    })
+                })
+                .collect();
+            println!(
+                "{}",
+                json!({ "ok": true, "pieces": names, "steps": steps, "at": at })
+            );
+        }
+        Command::Comm {
+            buffer,
+            first,
+            second,
+            moves,
+            max_length,
+            setup_depth,
+            num,
+            threads,
+        } => {
+            let stickers = [buffer, first, second].map(|name| Piece::sticker_named(name));
+            let [Ok(buffer), Ok(first), Ok(second)] = stickers else {
+                for err in stickers.iter().filter_map(|s| s.as_ref().err()) {
+                    error!("{}", err);
+                }
+                println!("{}", json!({ "ok": false }));
+                return;
+            };
+            let Some(allowed_ops) = parse_moves(moves) else {
+                error!("Invalid moves: {}", moves.as_deref().unwrap_or_default());
+                println!("{}", json!({ "ok": false }));
+                return;
+            };
+            let options = solver::CommutatorOptions {
+                max_depth: *max_length,
+                setup_depth: *setup_depth,
+                num: *num,
+                allowed_ops,
+                threads: num_threads(*threads),
+            };
+            let Some(comms) = solver::commutators(buffer, first, second, &options) else {
+                error!("Not a 3-cycle of the pieces of the same kind");
+                println!("{}", json!({ "ok": false }));
+                return;
+            };
+            let comms: Vec<_> = comms
+                .iter()
+                .map(|comm| {
+                    info!("{}", comm);
+                    let alg = comm.algorithm();
+                    json!({
+                        "commutator": comm.to_string(),
+                        "algorithm": format!("{}", alg),
+                        "length": alg.len(),
+                    })
+                })
+                .collect();
+            println!(
+                "{}",
+                json!({ "ok": !comms.is_empty(), "commutators": comms })
+            );
+        }
+        Command::Insert {
+            scramble,
+            skeleton,
+            alg,
+            moves,
+            max_length,
+            setup_depth,
+            num,
+            threads,
+        } => {
+            let (Some(scramble), Some(skeleton)) = (parse_alg(scramble), parse_alg(skeleton))
+            else {
+                error!("Invalid algorithm: {} / {}", scramble, skeleton);
+                println!("{}", json!({ "ok": false }));
+                return;
+            };
+            let Some(algorithms) = alg.iter().map(|a| parse_alg(a)).collect::<Option<Vec<_>>>()
+            else {
+                error!("Invalid algorithm: {}", alg.join(" / "));
+                println!("{}", json!({ "ok": false }));
+                return;
+            };
+            let Some(allowed_ops) = parse_moves(moves) else {
+                error!("Invalid moves: {}", moves.as_deref().unwrap_or_default());
+                println!("{}", json!({ "ok": false }));
+                return;
+            };
+            let leftover: Vec<String> = {
+                let mut ops = scramble.clone();
+                ops.extend(&skeleton);
+                ops.cycles().iter().map(|c| c.to_string()).collect()
+            };
+            info!("Leftover: {}", leftover.join(", "));
+            let options = solver::InsertionOptions {
+                algorithms,
+                commutators: solver::CommutatorOptions {
+                    max_depth: *max_length,
+                    setup_depth: *setup_depth,
+                    allowed_ops,
+                    ..solver::CommutatorOptions::default()
+                },
+                num: *num,
+                threads: num_threads(*threads),
+            };
+            let insertions: Vec<_> = solver::insertions(&scramble, &skeleton, &options)
+                .iter()
+                .map(|insertion| {
+                    info!(
+                        "{} at {}: {} ({} cancelled)",
+                        insertion.algorithm,
+                        insertion.position,
+                        insertion.solution,
+                        insertion.cancelled
+                    );
+                    json!({
+                        "position": insertion.position,
+                        "algorithm": format!("{}", insertion.algorithm),
+                        "commutator": insertion.commutator.as_ref().map(|c| c.to_string()),
+                        "solution": format!("{}", insertion.solution),
+                        "length": insertion.solution.len(),
+                        "cancelled": insertion.cancelled,
+                    })
+                })
+                .collect();
+            println!(
+                "{}",
+                json!({
+                    "ok": !insertions.is_empty(),
+                    "leftover": leftover,
+                    "insertions": insertions,
+                })
+            );
+        }
+    }
+}
+
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let (num, unit) = match s.find(|c: char| c.is_alphabetic()) {
+        Some(i) => s.split_at(i),
+        None => (s, "s"),
+    };
+    let num: f64 = num
+        .parse()
+        .map_err(|_| format!("Invalid duration: {}", s))?;
+    let secs = match unit {
+        "ms" => num / 1000.0,
+        "s" => num,
+        "m" => num * 60.0,
+        _ => return Err(format!("Invalid duration: {}", s)),
+    };
+    Ok(Duration::from_secs_f64(secs))
 }
 
 fn validation(c: &Cube, d: &Cube) -> Result<(), Color> {
@@ -80,7 +598,7 @@ fn validation(c: &Cube, d: &Cube) -> Result<(), Color> {
     let dount = d.count();
     let cwild = count[&Color::Wildcard];
     let dwild = dount[&Color::Wildcard];
-    for col in vec![
+    for col in [
         Color::Red,
         Color::Blue,
         Color::Yellow,
@@ -108,6 +626,11 @@ fn main() {
         .format_indent(Some(0))
         .init();
 
+    if let Some(command) = &opt.command {
+        run_command(command);
+        return;
+    }
+
     let mut allowed_ops = vec![];
     if opt.up {
         allowed_ops.push(Up(true));
@@ -182,23 +705,107 @@ fn main() {
         allowed_ops.push(Z(false));
     }
 
-    let (cube, goal) = read(cat().as_str());
-    info!("Init\n{}", &cube);
-    info!("Goal\n{}", &goal);
-    if let Err(col) = validation(&cube, &goal) {
-        error!("Validation Failed. Check number of color:{}.", col);
+    let input = cat();
+    let (mut cube, goals) = read_goals(input.as_str());
+    let goal = goals[0].clone();
+    let constraints = read_constraints(&input);
+    if !constraints.is_empty() && (opt.cfop || opt.roux || opt.blind.is_some()) {
+        error!("Avoid, Prefix, Suffix and Limit are only for the search");
         return;
     }
+    if goals.len() > 1 && (opt.cfop || opt.roux || opt.blind.is_some() || opt.generate) {
+        error!("Multiple Goal blocks are only for the search");
+        return;
+    }
+    let niss = if opt.inverse || opt.niss.is_some() {
+        if opt.cfop
+            || opt.roux
+            || opt.blind.is_some()
+            || goals.len() > 1
+            || opt.pre_auf
+            || opt.post_auf
+        {
+            error!("--inverse and --niss are only for the search with one goal, without AUFs");
+            return;
+        }
+        let Some(scramble) = read_scramble(&input) else {
+            error!("--inverse and --niss need Scramble");
+            return;
+        };
+        let skeleton = match &opt.niss {
+            None => Niss::default(),
+            Some(skeleton) => match parse_niss(skeleton) {
+                Ok((rest, niss)) if rest.trim().is_empty() => niss,
+                _ => {
+                    error!("Invalid NISS skeleton: {}", skeleton);
+                    return;
+                }
+            },
+        };
+        cube = skeleton.apply(&scramble, &goal, opt.inverse);
+        Some((scramble, skeleton))
+    } else {
+        None
+    };
+    info!("Init\n{}", &cube);
+    for goal in goals.iter() {
+        info!("Goal\n{}", goal);
+        if let Err(col) = validation(&cube, goal) {
+            error!("Validation Failed. Check number of color:{}.", col);
+            return;
+        }
+    }
+
+    let neutral = match &opt.neutral {
+        None => None,
+        Some(None) => Some(vec![
+            Color::White,
+            Color::Yellow,
+            Color::Red,
+            Color::Orange,
+            Color::Blue,
+            Color::Green,
+        ]),
+        Some(Some(colors)) => match parse_colors(colors) {
+            Ok(("", colors)) => Some(colors),
+            _ => {
+                error!("Invalid colors for neutral: {}", colors);
+                return;
+            }
+        },
+    };
+
+    let threads = num_threads(opt.threads);
 
     if opt.cfop {
-        if let Some(alg) = solver::cfop(&cube, opt.verbose) {
+        let options = solver::CfopOptions {
+            neutral,
+            xcross: opt.xcross,
+            xcross_depth: opt
+                .xcross_depth
+                .unwrap_or(solver::CfopOptions::default().xcross_depth),
+            easiest_first: opt.easiest_first,
+            multislot: opt.multislot,
+            threads,
+        };
+        if let Some((alg, steps)) = solver::cfop_with(&cube, &options, opt.verbose) {
+            let steps: Vec<_> = steps
+                .iter()
+                .map(|(name, alg)| {
+                    json!({
+                        "step": name,
+                        "algorithm": format!("{}", alg),
+                    })
+                })
+                .collect();
             println!(
                 "{}",
                 json!({
                     "ok": true,
                     "solution": {
                         "algorithm": format!("{}", alg),
-                        "length": alg.len(),
+                        "length": alg.weight(),
+                        "steps": steps,
                     }
                 })
             );
@@ -210,14 +817,15 @@ fn main() {
     }
 
     if opt.roux {
-        if let Some(alg) = solver::roux(&cube, opt.verbose) {
+        let options = solver::RouxOptions { neutral, threads };
+        if let Some(alg) = solver::roux_with(&cube, &options, opt.verbose) {
             println!(
                 "{}",
                 json!({
                     "ok": true,
                     "solution": {
                         "algorithm": format!("{}", alg),
-                        "length": alg.len(),
+                        "length": alg.weight(),
                     }
                 })
             );
@@ -228,6 +836,113 @@ fn main() {
         return;
     }
 
+    if let Some(method) = opt.blind {
+        let Some(solution) = solver::blind(&cube, method) else {
+            error!("Invalid cube: the pieces are not recognized by the centers");
+            println!("{}", json!({ "ok": false, "solution": {} }));
+            return;
+        };
+        let targets = |targets: &[solver::BlindTarget]| -> Vec<_> {
+            targets
+                .iter()
+                .map(|t| {
+                    json!({
+                        "letter": t.letter.to_string(),
+                        "setup": format!("{}", t.setup),
+                        "algorithm": format!("{}", t.algorithm),
+                    })
+                })
+                .collect()
+        };
+        let alg = solution.algorithm();
+        println!(
+            "{}",
+            json!({
+                "ok": true,
+                "solution": {
+                    "algorithm": format!("{}", alg),
+                    "length": alg.len(),
+                    "memo": {
+                        "corners": solution.corner_memo(),
+                        "edges": solution.edge_memo(),
+                        "parity": solution.parity,
+                    },
+                    "corners": targets(&solution.corners),
+                    "edges": targets(&solution.edges),
+                }
+            })
+        );
+        return;
+    }
+
+    if opt.generate {
+        let mut move_sets = vec![];
+        if !allowed_ops.is_empty() {
+            move_sets.push(allowed_ops);
+        }
+        for moves in opt.moves.iter() {
+            match parse_moves(&Some(moves.clone())) {
+                Some(ops) if !ops.is_empty() => move_sets.push(ops),
+                _ => {
+                    error!("Invalid moves: {}", moves);
+                    return;
+                }
+            }
+        }
+        if move_sets.is_empty() {
+            error!("No Operations specified");
+            return;
+        }
+        let options = solver::GeneratorOptions {
+            max_depth: opt.max_depth,
+            max_length: opt.max_length,
+            num: opt.num,
+            metric: opt.metric,
+            ergonomics: opt.ergonomics.clone(),
+            constraints: constraints.clone(),
+            equivalence: EquivalenceOptions {
+                auf: opt.modulo_auf,
+                ..EquivalenceOptions::default()
+            },
+            budget: solver::Budget {
+                timeout: opt.timeout,
+                max_nodes: opt.max_nodes,
+                cancel: None,
+            },
+            threads,
+        };
+        let algs = solver::generate(&cube, &goal, &move_sets, &options);
+        if let Some(path) = &opt.export {
+            let lines: String = algs.iter().map(|alg| format!("{}\n", alg)).collect();
+            if let Err(err) = std::fs::write(path, lines) {
+                error!("Cannot write {}: {}", path.display(), err);
+                return;
+            }
+        }
+        let algorithms: Vec<_> = algs
+            .iter()
+            .map(|alg| {
+                let mut algorithm = json!({
+                    "algorithm": format!("{}", alg),
+                    "length": alg.length_in(opt.metric),
+                });
+                if let Some(ergonomics) = &opt.ergonomics {
+                    algorithm["cost"] = json!(alg.cost(ergonomics));
+                }
+                algorithm
+            })
+            .collect();
+        println!(
+            "{}",
+            json!({
+                "ok": !algorithms.is_empty(),
+                "metric": opt.metric.to_string(),
+                "algorithms": algorithms,
+            })
+        );
+        return;
+    }
+
     if allowed_ops.is_empty() {
         error!("No Operations specified");
         return;
@@ -237,25 +952,107 @@ fn main() {
         warn!("Too large max_depth: {}", opt.max_depth);
     }
 
-    let algorithms = solver::search(
-        &cube,
-        &goal,
-        allowed_ops,
-        opt.max_depth,
-        opt.num,
-        opt.verbose,
-    );
-    if !algorithms.is_empty() {
-        let mut solutions = vec![];
-        for ops in algorithms.iter() {
-            solutions.push(json!({
+    let options = solver::SearchOptions {
+        max_depth: opt.max_depth,
+        num: opt.num,
+        budget: solver::Budget {
+            timeout: opt.timeout,
+            max_nodes: opt.max_nodes,
+            cancel: None,
+        },
+        threads: if opt.stream { 1 } else { threads },
+        symmetry: opt.symmetry,
+        ergonomics: opt.ergonomics.clone(),
+        slack: opt.slack.unwrap_or(solver::SearchOptions::default().slack),
+        pre_auf: opt.pre_auf,
+        post_auf: opt.post_auf,
+        constraints,
+        verbose: opt.verbose,
+    };
+    // The AUFs separated from the solution, and the goal reached with several goals
+    let annotate = |ops: &Ops, solution: &mut serde_json::Value| {
+        let mut full = ops.clone();
+        if opt.pre_auf || opt.post_auf {
+            if let Some((pre, post)) = solver::split_auf(&cube, &goals, ops) {
+                let auf = |ops: &Ops| {
+                    if ops.is_empty() {
+                        String::new()
+                    } else {
+                        ops.to_string()
+                    }
+                };
+                solution["pre_auf"] = json!(auf(&pre));
+                solution["post_auf"] = json!(auf(&post));
+                full = pre;
+                full.extend(ops);
+                full.extend(&post);
+            }
+        }
+        if goals.len() > 1 {
+            solution["goal"] = json!(solver::reached_goal(&cube, &goals, &full));
+        }
+    };
+    let result = if options.threads > 1 {
+        solver::search_goals(&cube, &goals, allowed_ops, &options)
+    } else {
+        solver::Searcher::with_goals(&cube, &goals, allowed_ops, &options).run(|event| {
+            if !opt.stream {
+                return;
+            }
+            match event {
+                solver::SearchEvent::Solution(ops) => {
+                    let mut solution = json!({
+                        "event": "solution",
                         "algorithm": format!("{}", ops),
                         "length": ops.len(),
-            }));
+                    });
+                    annotate(ops, &mut solution);
+                    println!("{}", solution);
+                }
+                solver::SearchEvent::Progress(progress) => println!(
+                    "{}",
+                    json!({
+                        "event": "progress",
+                        "depth": progress.depth,
+                        "nodes": progress.nodes,
+                        "from_start": progress.from_start,
+                        "from_goal": progress.from_goal,
+                    })
+                ),
+            }
+        })
+    };
+    if !result.solutions.is_empty() {
+        let mut solutions = vec![];
+        for ops in result.solutions.iter() {
+            let mut solution = json!({
+                "algorithm": format!("{}", ops),
+                "length": ops.len(),
+            });
+            if let Some(ergonomics) = &opt.ergonomics {
+                solution["cost"] = json!(ops.cost(ergonomics));
+            }
+            annotate(ops, &mut solution);
+            if let Some((scramble, skeleton)) = &niss {
+                let mut skeleton = skeleton.clone();
+                skeleton.push(ops.clone(), opt.inverse);
+                let combined = skeleton.solution();
+                solution["niss"] = json!(skeleton.to_string());
+                solution["solution"] = json!(format!("{}", combined));
+                solution["solution_length"] = json!(combined.len());
+                solution["solves"] = json!(skeleton.solves(scramble, &goal));
+            }
+            solutions.push(solution);
         }
-        println!("{}", json!({ "ok": true, "solutions": solutions, }));
+        println!(
+            "{}",
+            json!({ "ok": true, "solutions": solutions, "cutoff": result.cutoff })
+        );
     } else {
         info!("No Solution");
-        println!("{}", json!({ "ok": false, "solutions": [], }));
+        println!(
+            "{}",
+            json!({ "ok": false, "solutions": [], "cutoff": result.cutoff })
+        );
     }
 }
@@ -1,11 +1,11 @@
-use crate::cube;
 use crate::entities::*;
 use log::error;
 
 use nom::{
     branch::alt,
     bytes::complete::{is_not, tag, take_while},
-    combinator::{eof, map, opt, value},
+    character::complete::digit1,
+    combinator::{eof, map, map_res, opt, value},
     multi::{many0, many1},
     sequence::{delimited, preceded, tuple},
     IResult,
@@ -16,6 +16,10 @@ enum Entry {
     Init(Cube),
     Goal(Cube),
     Scramble(Ops),
+    Avoid(Ops),
+    Prefix(Ops),
+    Suffix(Ops),
+    Limit(Ops, usize),
 }
 
 pub fn cat() -> String {
@@ -32,18 +36,15 @@ pub fn cat() -> String {
     buf
 }
 
-pub fn read(mut buf: &str) -> (Cube, Cube) {
-    let canonical = cube![
-        Y Y Y;
-        Y Y Y;
-        Y Y Y;
-        R R R G G G O O O B B B;
-        R R R G G G O O O B B B;
-        R R R G G G O O O B B B;
-        W W W;
-        W W W;
-        W W W;
-    ];
+pub fn read(buf: &str) -> (Cube, Cube) {
+    let (init, mut goals) = read_goals(buf);
+    (init, goals.swap_remove(0))
+}
+
+/// Init and the goals: each Goal block is an alternative (the solved cube without any).
+/// Scramble is applied to the first goal.
+pub fn read_goals(mut buf: &str) -> (Cube, Vec<Cube>) {
+    let canonical = Cube::solved();
     let mut entries = vec![];
     while let Ok((rest, e)) = parse_entry(buf) {
         entries.push(e);
@@ -55,16 +56,16 @@ pub fn read(mut buf: &str) -> (Cube, Cube) {
             panic!("Parse Error");
         }
     }
-    let mut goal = canonical.clone();
+    let mut goals = vec![];
     for e in entries.iter() {
-        match e {
-            Entry::Goal(c) => {
-                goal = c.clone();
-            }
-            _ => {}
+        if let Entry::Goal(c) = e {
+            goals.push(c.clone());
         }
     }
-    let mut init = goal.clone();
+    if goals.is_empty() {
+        goals.push(canonical);
+    }
+    let mut init = goals[0].clone();
     let mut initialized = 0;
     for e in entries.iter() {
         match e {
@@ -73,7 +74,7 @@ pub fn read(mut buf: &str) -> (Cube, Cube) {
                 initialized += 1;
             }
             Entry::Scramble(ops) => {
-                init = ops.apply(&goal);
+                init = ops.apply(&goals[0]);
                 initialized += 1;
             }
             _ => {}
@@ -83,11 +84,11 @@ pub fn read(mut buf: &str) -> (Cube, Cube) {
         error!("Init or Scramble must exist uniquely.");
         panic!("Input is invalid");
     }
-    (init, goal)
+    (init, goals)
 }
 
 fn parse_entry(input: &str) -> IResult<&str, Entry> {
-    alt((parse_init, parse_goal, parse_scramble))(input)
+    alt((parse_init, parse_goal, parse_scramble, parse_constraint))(input)
 }
 
 fn parse_init(input: &str) -> IResult<&str, Entry> {
@@ -102,7 +103,7 @@ fn parse_init(input: &str) -> IResult<&str, Entry> {
             parse_cube,
             tuple((commentable_spaces, tag("}"))),
         ),
-        |cube| Entry::Init(cube),
+        Entry::Init,
     )(input)
 }
 
@@ -118,7 +119,7 @@ fn parse_goal(input: &str) -> IResult<&str, Entry> {
             parse_cube,
             tuple((commentable_spaces, tag("}"))),
         ),
-        |cube| Entry::Goal(cube),
+        Entry::Goal,
     )(input)
 }
 
@@ -134,10 +135,43 @@ fn parse_scramble(input: &str) -> IResult<&str, Entry> {
             parse_ops,
             tuple((commentable_spaces, tag("}"))),
         ),
-        |ops| Entry::Scramble(ops),
+        Entry::Scramble,
     )(input)
 }
 
+/// `Avoid { F B }`, `Prefix { R U' }`, `Suffix { U }` or `Limit { x y z: 2 }`
+fn parse_constraint(input: &str) -> IResult<&str, Entry> {
+    let block = |name: &'static str| {
+        delimited(
+            tuple((commentable_spaces, tag(name), commentable_spaces, tag("{"))),
+            parse_ops,
+            tuple((commentable_spaces, tag("}"))),
+        )
+    };
+    let limit = delimited(
+        tuple((
+            commentable_spaces,
+            tag("Limit"),
+            commentable_spaces,
+            tag("{"),
+        )),
+        tuple((
+            parse_ops,
+            preceded(
+                tuple((commentable_spaces, tag(":"), commentable_spaces)),
+                map_res(digit1, str::parse),
+            ),
+        )),
+        tuple((commentable_spaces, tag("}"))),
+    );
+    alt((
+        map(block("Avoid"), Entry::Avoid),
+        map(block("Prefix"), Entry::Prefix),
+        map(block("Suffix"), Entry::Suffix),
+        map(limit, |(ops, max)| Entry::Limit(ops, max)),
+    ))(input)
+}
+
 fn parse_cube(input: &str) -> IResult<&str, Cube> {
     map(many1(parse_color), |colors: Vec<Color>| {
         assert!(colors.len() == 6 * 9);
@@ -250,6 +284,56 @@ pub fn parse_ops(input: &str) -> IResult<&str, Ops> {
     Ok((rest, Ops::new(opss.concat())))
 }
 
+/// Ops on the normal scramble and the inverse one in parentheses (e.g. `R U (F' D) L`)
+pub fn parse_niss(input: &str) -> IResult<&str, Niss> {
+    let inverse = delimited(
+        tuple((commentable_spaces, tag("("))),
+        parse_ops,
+        tuple((commentable_spaces, tag(")"))),
+    );
+    let (rest, segments) = many1(alt((
+        map(inverse, |ops| (ops, true)),
+        map(parse_ops, |ops| (ops, false)),
+    )))(input)?;
+    let mut niss = Niss::default();
+    for (ops, inverse) in segments {
+        niss.push(ops, inverse);
+    }
+    Ok((rest, niss))
+}
+
+/// The ops of the Scramble entry, if any
+pub fn read_scramble(mut buf: &str) -> Option<Ops> {
+    let mut scramble = None;
+    while let Ok((rest, e)) = parse_entry(buf) {
+        if let Entry::Scramble(ops) = e {
+            scramble = Some(ops);
+        }
+        buf = rest;
+    }
+    scramble
+}
+
+/// Constraints in the input (each Avoid and Limit block is one)
+pub fn read_constraints(mut buf: &str) -> Constraints {
+    let mut constraints = Constraints::default();
+    while let Ok((rest, e)) = parse_entry(buf) {
+        match e {
+            Entry::Avoid(ops) => constraints.avoid.push(ops),
+            Entry::Prefix(ops) => constraints.prefix = ops,
+            Entry::Suffix(ops) => constraints.suffix = ops,
+            Entry::Limit(ops, max) => constraints.limits.push((ops, max)),
+            _ => {}
+        }
+        buf = rest;
+    }
+    constraints
+}
+
+pub fn parse_colors(input: &str) -> IResult<&str, Vec<Color>> {
+    many1(parse_color)(input)
+}
+
 fn parse_color(input: &str) -> IResult<&str, Color> {
     use Color::*;
     preceded(
@@ -389,6 +473,62 @@ mod test_read {
         );
     }
 
+    #[test]
+    fn test_goals() {
+        let (yellow, any) = ("Y".repeat(54), "*".repeat(54));
+        let input = format!("Scramble {{ U }} Goal {{ {} }} Goal {{ {} }}", yellow, any);
+        let (init, goals) = read_goals(&input);
+        assert_eq!(goals.len(), 2);
+        assert_eq!(init, goals[0]);
+        assert!(goals[1].has_wildcard());
+        let (init, goals) = read_goals("Scramble { U }");
+        assert_eq!(goals, vec![Cube::solved()]);
+        assert_ne!(init, goals[0]);
+    }
+
+    #[test]
+    fn test_constraints() {
+        let input = "
+            Scramble { R U R' }
+            Avoid { F B }  # comment
+            Avoid { x x }
+            Prefix { R U' }
+            Suffix { U }
+            Limit { x y z: 2 }
+            Limit { F: 0 }
+        ";
+        let constraints = read_constraints(input);
+        let ops = |s| parse_ops(s).unwrap().1;
+        assert_eq!(constraints.avoid, vec![ops("F B"), ops("x x")]);
+        assert_eq!(constraints.prefix, ops("R U'"));
+        assert_eq!(constraints.suffix, ops("U"));
+        assert_eq!(constraints.limits, vec![(ops("x y z"), 2), (ops("F"), 0)]);
+        assert!(constraints.allows(&ops("R U' x U")));
+        assert!(!constraints.allows(&ops("R U' x2 U")));
+        let (init, goal) = read(input);
+        assert_eq!(init, ops("R U R'").apply(&goal));
+    }
+
+    #[test]
+    fn test_niss() {
+        use Operation::*;
+        let (rest, niss) = parse_niss("R U (F' D) L").unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(
+            niss.segments,
+            vec![
+                (Ops::new(vec![Right(true), Up(true)]), false),
+                (Ops::new(vec![Front(false), Down(true)]), true),
+                (Ops::new(vec![Left(true)]), false),
+            ]
+        );
+        assert!(parse_niss("(R").is_err());
+        assert_eq!(
+            read_scramble("Scramble { R U' }"),
+            Some(Ops::new(vec![Right(true), Up(false)]))
+        );
+    }
+
     #[test]
     fn test_color() {
         use Color::*;
@@ -396,6 +536,8 @@ mod test_read {
         assert_ok!(parse_color("W"), White);
         assert_ok!(parse_color("."), Other);
         assert_ok!(parse_color("*"), Wildcard);
+        assert_ok!(parse_colors("WY"), vec![White, Yellow]);
+        assert_ok!(parse_colors("w y r"), vec![White, Yellow, Red]);
     }
 
     #[test]
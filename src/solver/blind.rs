@@ -0,0 +1,473 @@
+use crate::entities::*;
+use crate::read;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::OnceLock;
+
+/// Method of the edges. The corners are always solved by Old Pochmann.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlindMethod {
+    /// T perm from the buffer UR to UL
+    OldPochmann,
+    /// M2 from the buffer DF to UB
+    M2,
+}
+
+impl std::str::FromStr for BlindMethod {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "op" | "OP" => Ok(BlindMethod::OldPochmann),
+            "m2" | "M2" => Ok(BlindMethod::M2),
+            _ => Err(format!("Invalid blind method: {} (op or m2)", s)),
+        }
+    }
+}
+
+/// A target of the memo and the algorithm to shoot it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlindTarget {
+    /// Speffz letter of the sticker
+    pub letter: char,
+    pub setup: Ops,
+    /// The setup, the swap and the undo of the setup
+    pub algorithm: Ops,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlindSolution {
+    pub corners: Vec<BlindTarget>,
+    pub edges: Vec<BlindTarget>,
+    /// Odd num of the corner targets. The Y perms leave UL and UB swapped,
+    /// which is included in the memo of the edges.
+    pub parity: bool,
+}
+
+impl BlindSolution {
+    pub fn algorithm(&self) -> Ops {
+        let mut ops = Ops::default();
+        for target in self.corners.iter().chain(self.edges.iter()) {
+            ops.extend(&target.algorithm);
+        }
+        ops
+    }
+
+    /// Letter pairs (e.g. "AB CD E")
+    pub fn corner_memo(&self) -> String {
+        memo_string(&self.corners)
+    }
+
+    pub fn edge_memo(&self) -> String {
+        memo_string(&self.edges)
+    }
+}
+
+fn memo_string(targets: &[BlindTarget]) -> String {
+    let letters: Vec<char> = targets.iter().map(|t| t.letter).collect();
+    letters
+        .chunks(2)
+        .map(|pair| pair.iter().collect::<String>())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Stickers of the Speffz letters (A to X: U, L, F, R, B, D faces, clockwise from the top left)
+fn speffz(corner: bool) -> Vec<usize> {
+    use FaceIndex::*;
+    let facelets = if corner {
+        [(0, 0), (0, 2), (2, 2), (2, 0)]
+    } else {
+        [(0, 1), (1, 2), (2, 1), (1, 0)]
+    };
+    let mut stickers = vec![];
+    for face in [Up, Left, Front, Right, Back, Down] {
+        for (i, j) in facelets {
+            stickers.push(Cube::sticker(face, i, j));
+        }
+    }
+    stickers
+}
+
+fn letter_of(sticker: usize, corner: bool) -> char {
+    let i = speffz(corner).iter().position(|&k| k == sticker).unwrap();
+    (b'A' + i as u8) as char
+}
+
+fn sticker_of(letter: char, corner: bool) -> usize {
+    speffz(corner)[(letter as u8 - b'A') as usize]
+}
+
+/// Sticker permutation from the solved cube, by the colors of the centers.
+/// None if a piece is not found.
+fn state(cube: &Cube) -> Option<Permutation> {
+    let stickers = cube.stickers();
+    let center = |face: FaceIndex| stickers[Cube::sticker(face, 1, 1)];
+    let mut centers: Vec<Color> = crate::util::centers()
+        .into_iter()
+        .map(|(face, _, _)| center(face))
+        .collect();
+    centers.sort();
+    centers.dedup();
+    if centers.len() != 6 {
+        return None;
+    }
+    let mut sources = *Permutation::identity().sources();
+    for position in Piece::all() {
+        let colors: Vec<Color> = position.stickers().iter().map(|&k| stickers[k]).collect();
+        let n = colors.len();
+        let (piece, o) = Piece::all()
+            .into_iter()
+            .filter(|piece| piece.is_corner() == position.is_corner())
+            .flat_map(|piece| (0..n).map(move |o| (piece, o)))
+            .find(|(piece, o)| {
+                let facelets = piece.facelets();
+                (0..n).all(|k| colors[k] == center(facelets[(k + o) % n].0))
+            })?;
+        let piece_stickers = piece.stickers();
+        for (k, &s) in position.stickers().iter().enumerate() {
+            sources[s] = piece_stickers[(k + o) % n] as u8;
+        }
+    }
+    Permutation::from_sources(&sources)
+}
+
+/// Swap the pieces of the stickers `a` and `b`, so that `a` goes to `b`
+fn swap(a: usize, b: usize) -> Permutation {
+    let (pa, ia) = Piece::of_sticker(a).unwrap();
+    let (pb, ib) = Piece::of_sticker(b).unwrap();
+    let (sa, sb) = (pa.stickers(), pb.stickers());
+    let n = sa.len();
+    let mut sources = *Permutation::identity().sources();
+    for k in 0..n {
+        let (x, y) = (sa[(ia + k) % n], sb[(ib + k) % n]);
+        sources[x] = y as u8;
+        sources[y] = x as u8;
+    }
+    Permutation::from_sources(&sources).unwrap()
+}
+
+fn is_solved(state: &Permutation, piece: Piece) -> bool {
+    piece.stickers().iter().all(|&k| state.source(k) == k)
+}
+
+/// Targets from the buffer, breaking into the first unsolved piece when the buffer
+/// comes back. Returns the targets and the state after them.
+fn memo(state: &Permutation, buffer: usize, corner: bool) -> (Vec<usize>, Permutation) {
+    let (buffer_piece, _) = Piece::of_sticker(buffer).unwrap();
+    let mut state = *state;
+    let mut targets = vec![];
+    loop {
+        let s = state.source(buffer);
+        let target = if Piece::of_sticker(s).unwrap().0 != buffer_piece {
+            s
+        } else {
+            let unsolved = speffz(corner).into_iter().find(|&k| {
+                let piece = Piece::of_sticker(k).unwrap().0;
+                piece != buffer_piece && !is_solved(&state, piece)
+            });
+            match unsolved {
+                Some(k) => k,
+                None => break,
+            }
+        };
+        targets.push(target);
+        state = state.then(&swap(buffer, target));
+    }
+    (targets, state)
+}
+
+/// Shortest setups (up to `depth` turns) which move each sticker to `helper`
+/// and keep the stickers `fixed`
+fn setups(ops: &[Operation], depth: usize, helper: usize, fixed: &[usize]) -> BTreeMap<usize, Ops> {
+    let mut turns = vec![];
+    for op in ops.iter() {
+        for turn in [
+            vec![op.clone()],
+            vec![op.clone(), op.clone()],
+            vec![op.rev()],
+        ] {
+            let perm = Permutation::of_ops(&Ops::new(turn.clone()));
+            turns.push((turn, perm));
+        }
+    }
+    let mut setups = BTreeMap::new();
+    let mut visited = HashSet::new();
+    let mut layer = vec![(Ops::default(), Permutation::identity())];
+    visited.insert(*Permutation::identity().sources());
+    for d in 0..=depth {
+        let mut next = vec![];
+        for (setup, perm) in layer.iter() {
+            if fixed.iter().all(|&k| perm.source(k) == k) {
+                setups
+                    .entry(perm.source(helper))
+                    .or_insert_with(|| setup.clone());
+            }
+            if d == depth {
+                continue;
+            }
+            for (turn, turn_perm) in turns.iter() {
+                let perm = perm.then(turn_perm);
+                if !visited.insert(*perm.sources()) {
+                    continue;
+                }
+                let mut setup = setup.clone();
+                for op in turn.iter() {
+                    setup.push(op.clone());
+                }
+                next.push((setup, perm));
+            }
+        }
+        layer = next;
+    }
+    setups
+}
+
+fn compound(name: &str, ops: &str) -> Operation {
+    Operation::Compound(name.to_string(), true, read::parse_ops(ops).unwrap().1.data)
+}
+
+fn stickers_of(pieces: &[&str]) -> Vec<usize> {
+    pieces
+        .iter()
+        .flat_map(|name| name.parse::<Piece>().unwrap().stickers())
+        .collect()
+}
+
+/// How to shoot the targets from a buffer
+struct Shooter {
+    buffer: usize,
+    corner: bool,
+    swap: Operation,
+    setups: BTreeMap<usize, Ops>,
+    /// Algorithms of the targets which are not a setup and the swap (the M slice)
+    specials: Vec<(char, Ops)>,
+}
+
+impl Shooter {
+    /// Old Pochmann corners: modified Y perm from A (UBL) to P (DFR), swapping UL and UB
+    fn corners() -> Self {
+        use Operation::*;
+        let faces = [
+            Up(true),
+            Down(true),
+            Front(true),
+            Back(true),
+            Left(true),
+            Right(true),
+        ];
+        let buffer = sticker_of('A', true);
+        Self {
+            buffer,
+            corner: true,
+            swap: compound("Y", "RU'R'U' RUR'F' RUR'U' R'FR"),
+            setups: setups(
+                &faces,
+                3,
+                sticker_of('P', true),
+                &stickers_of(&["ULB", "UL", "UB"]),
+            ),
+            specials: vec![],
+        }
+    }
+
+    /// Old Pochmann edges: T perm from B (UR) to D (UL), swapping URF and UBR
+    fn edges_op() -> Self {
+        use Operation::*;
+        let layers = [
+            Up(true),
+            Down(true),
+            Front(true),
+            Back(true),
+            Left(true),
+            Right(true),
+            Middle(true),
+            Equator(true),
+            Standing(true),
+        ];
+        Self {
+            buffer: sticker_of('B', false),
+            corner: false,
+            swap: compound("T", "RUR'U' R'FR2U' R'U'RU R'F'"),
+            setups: setups(
+                &layers,
+                4,
+                sticker_of('D', false),
+                &stickers_of(&["UR", "URF", "UBR"]),
+            ),
+            specials: vec![],
+        }
+    }
+
+    /// M2 edges from U (DF) to A (UB)
+    fn edges_m2() -> Self {
+        use Operation::*;
+        let faces = [
+            Up(true),
+            Down(true),
+            Front(true),
+            Back(true),
+            Left(true),
+            Right(true),
+        ];
+        let special = |ops: &str| read::parse_ops(ops).unwrap().1;
+        Self {
+            buffer: sticker_of('U', false),
+            corner: false,
+            swap: compound("M2", "M2"),
+            setups: setups(
+                &faces,
+                4,
+                sticker_of('A', false),
+                &stickers_of(&["DF", "UF", "DB"]),
+            ),
+            specials: vec![
+                ('C', special("U2 M' U2 M'")),
+                ('W', special("M U2 M U2")),
+                ('I', special("D M' U R2 U' M U R2 U' D' M2")),
+                ('S', special("M2 D U R2 U' M' U R2 U' M D'")),
+                ('Q', special("U B' R U' B M2 B' U R' B U'")),
+            ],
+        }
+    }
+
+    /// The algorithm which moves the buffer to `target`
+    fn shoot(&self, target: usize) -> BlindTarget {
+        let letter = letter_of(target, self.corner);
+        if let Some((_, alg)) = self.specials.iter().find(|(c, _)| *c == letter) {
+            return BlindTarget {
+                letter,
+                setup: Ops::default(),
+                algorithm: alg.clone(),
+            };
+        }
+        let setup = self.setups[&target].clone();
+        let mut algorithm = setup.clone();
+        algorithm.push(self.swap.clone());
+        algorithm.extend(&setup.rev());
+        BlindTarget {
+            letter,
+            setup,
+            algorithm,
+        }
+    }
+}
+
+static CORNERS: OnceLock<Shooter> = OnceLock::new();
+static EDGES_OP: OnceLock<Shooter> = OnceLock::new();
+static EDGES_M2: OnceLock<Shooter> = OnceLock::new();
+
+/// Memo and the solution, the corners first by Old Pochmann and then the edges.
+/// None if the cube is not recognized (e.g. no unique centers, or other colors).
+pub fn blind(cube: &Cube, method: BlindMethod) -> Option<BlindSolution> {
+    let state = state(cube)?;
+
+    let shooter = CORNERS.get_or_init(Shooter::corners);
+    let (targets, _) = memo(&state, shooter.buffer, true);
+    let corners: Vec<BlindTarget> = targets.iter().map(|&t| shooter.shoot(t)).collect();
+    let mut state = state;
+    for target in corners.iter() {
+        state = state.then(&Permutation::of_ops(&target.algorithm));
+    }
+
+    let shooter = match method {
+        BlindMethod::OldPochmann => EDGES_OP.get_or_init(Shooter::edges_op),
+        BlindMethod::M2 => EDGES_M2.get_or_init(Shooter::edges_m2),
+    };
+    let (targets, _) = memo(&state, shooter.buffer, false);
+    let edges = targets
+        .iter()
+        .enumerate()
+        .map(|(i, &t)| {
+            // The second of a pair in the M slice is moved by the first M2
+            let letter = match (method, i % 2, letter_of(t, false)) {
+                (BlindMethod::M2, 1, 'C') => 'W',
+                (BlindMethod::M2, 1, 'W') => 'C',
+                (BlindMethod::M2, 1, 'I') => 'S',
+                (BlindMethod::M2, 1, 'S') => 'I',
+                (_, _, letter) => letter,
+            };
+            let mut target = shooter.shoot(sticker_of(letter, false));
+            target.letter = letter_of(t, false);
+            target
+        })
+        .collect();
+
+    Some(BlindSolution {
+        parity: corners.len() % 2 == 1,
+        corners,
+        edges,
+    })
+}
+
+#[cfg(test)]
+mod test_blind {
+    use crate::read;
+    use crate::solver::blind::*;
+
+    #[test]
+    fn test_swap_algorithms() {
+        // The swap algorithms move the buffer to the helper
+        for (alg, a, b, corner) in [
+            ("RU'R'U' RUR'F' RUR'U' R'FR", 'A', 'P', true),
+            ("RUR'U' R'FR2U' R'U'RU R'F'", 'B', 'D', false),
+        ] {
+            let perm = Permutation::of_ops(&read::parse_ops(alg).unwrap().1);
+            let (a, b) = (sticker_of(a, corner), sticker_of(b, corner));
+            assert_eq!(perm.source(b), a);
+            assert_eq!(perm.source(a), b);
+        }
+    }
+
+    #[test]
+    fn test_setups() {
+        // All the targets but the buffer piece can be shot
+        for (shooter, buffer) in [
+            (Shooter::corners(), "ULB"),
+            (Shooter::edges_op(), "UR"),
+            (Shooter::edges_m2(), "DF"),
+        ] {
+            let buffer: Piece = buffer.parse().unwrap();
+            for k in speffz(shooter.corner) {
+                if Piece::of_sticker(k).unwrap().0 == buffer {
+                    continue;
+                }
+                let letter = letter_of(k, shooter.corner);
+                if shooter.specials.iter().any(|(c, _)| *c == letter) {
+                    continue;
+                }
+                // The buffer goes to the target
+                let target = shooter.shoot(k);
+                let perm = Permutation::of_ops(&target.algorithm);
+                assert_eq!(perm.source(k), shooter.buffer, "{}", letter);
+            }
+        }
+    }
+
+    #[test]
+    fn test_blind() {
+        let (solved, _) = read::read("Scramble { U U' }");
+        let solution = blind(&solved, BlindMethod::OldPochmann).unwrap();
+        assert!(solution.algorithm().is_empty());
+
+        // T perm
+        let (cube, _) = read::read("Scramble { R U R' U' R' F R2 U' R' U' R U R' F' }");
+        let solution = blind(&cube, BlindMethod::OldPochmann).unwrap();
+        // UL and UB are swapped by the odd num of Y perms
+        assert_eq!(solution.edge_memo(), "DA");
+        assert_eq!(solution.corner_memo(), "BC B");
+        assert!(solution.parity);
+
+        for scramble in [
+            "R U F' L D B2",
+            "D2 F' R2 U B' L2 F U' R D' L B2 U F2",
+            "F R' U2 L B D' F2 R L' U B D2 R' F L",
+            "U F2 L' B R2 D F' U2 L D' B' R U'",
+        ] {
+            let (cube, goal) = read::read(&format!("Scramble {{ {} }}", scramble));
+            for method in [BlindMethod::OldPochmann, BlindMethod::M2] {
+                let solution = blind(&cube, method).unwrap();
+                assert_eq!(solution.algorithm().apply(&cube), goal);
+                assert_eq!(solution.edges.len() % 2, 0);
+            }
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Handle to cancel a running search (from another thread)
+#[derive(Debug, Clone, Default)]
+pub struct Cancel(Arc<AtomicBool>);
+
+impl Cancel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Limits of a search.
+/// `timeout` is not available on wasm (no clock); use `max_nodes` there.
+#[derive(Debug, Clone, Default)]
+pub struct Budget {
+    pub timeout: Option<Duration>,
+    pub max_nodes: Option<usize>,
+    pub cancel: Option<Cancel>,
+}
+
+impl Budget {
+    pub fn timeout(timeout: Duration) -> Self {
+        Self {
+            timeout: Some(timeout),
+            ..Self::default()
+        }
+    }
+    pub fn max_nodes(max_nodes: usize) -> Self {
+        Self {
+            max_nodes: Some(max_nodes),
+            ..Self::default()
+        }
+    }
+    pub fn cancel(cancel: &Cancel) -> Self {
+        Self {
+            cancel: Some(cancel.clone()),
+            ..Self::default()
+        }
+    }
+    pub(crate) fn start(&self) -> Meter {
+        Meter {
+            max_nodes: self.max_nodes,
+            cancel: self.cancel.clone(),
+            deadline: self.timeout.map(|timeout| Instant::now() + timeout),
+            nodes: AtomicUsize::new(0),
+        }
+    }
+}
+
+/// Consumption of a Budget during one search (shared by threads)
+pub(crate) struct Meter {
+    max_nodes: Option<usize>,
+    cancel: Option<Cancel>,
+    deadline: Option<Instant>,
+    nodes: AtomicUsize,
+}
+
+impl Meter {
+    pub fn nodes(&self) -> usize {
+        self.nodes.load(Ordering::Relaxed)
+    }
+    /// Count one node, and check the budget.
+    /// `ticks` is the count of the caller (one per worker): the clock and the cancel
+    /// are checked on its first tick and every 256 ticks after.
+    /// Returns false when the search should stop.
+    pub fn tick(&self, ticks: &mut usize) -> bool {
+        let nodes = self.nodes.fetch_add(1, Ordering::Relaxed) + 1;
+        if let Some(max_nodes) = self.max_nodes {
+            if nodes > max_nodes {
+                return false;
+            }
+        }
+        let check = ticks.is_multiple_of(256);
+        *ticks += 1;
+        if check {
+            if let Some(cancel) = &self.cancel {
+                if cancel.is_cancelled() {
+                    return false;
+                }
+            }
+            if let Some(deadline) = self.deadline {
+                if Instant::now() >= deadline {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod test_budget {
+    use crate::solver::{Budget, Cancel};
+    use std::time::Duration;
+
+    #[test]
+    fn test_meter() {
+        let budget = Budget::max_nodes(10);
+        let meter = budget.start();
+        let mut ticks = 0;
+        for _ in 0..10 {
+            assert!(meter.tick(&mut ticks));
+        }
+        assert!(!meter.tick(&mut ticks));
+
+        let cancel = Cancel::new();
+        let budget = Budget::cancel(&cancel);
+        let meter = budget.start();
+        let mut ticks = 0;
+        assert!((0..1000).all(|_| meter.tick(&mut ticks)));
+        cancel.cancel();
+        assert!(!(0..1000).all(|_| meter.tick(&mut ticks)));
+
+        let budget = Budget::timeout(Duration::from_millis(0));
+        let meter = budget.start();
+        assert!(!meter.tick(&mut 0));
+
+        // Each worker checks the clock, though the others count most of the nodes
+        let budget = Budget::timeout(Duration::from_millis(0));
+        let meter = budget.start();
+        let mut ticks = 0;
+        for _ in 0..255 {
+            meter.tick(&mut ticks);
+        }
+        assert!(!meter.tick(&mut 0));
+    }
+}
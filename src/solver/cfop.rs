@@ -1,13 +1,186 @@
 use crate::cube;
 use crate::entities::*;
 use crate::read;
-use crate::solver::{search_any, search_one};
+use crate::solver::neutral::neutral;
+use crate::solver::{search_any_with, search_one, SearchOptions};
+use crate::util;
 use log::info;
 
+#[derive(Debug, Clone)]
+pub struct CfopOptions {
+    /// Color neutral: colors to try on the down face
+    pub neutral: Option<Vec<Color>>,
+    /// Solve the cross together with the first pair
+    pub xcross: bool,
+    /// Max depth of the X-cross search (from each side)
+    pub xcross_depth: usize,
+    /// Solve the easiest slot first, instead of FL, BL, then FR and BR together
+    pub easiest_first: bool,
+    /// Also try to solve two pairs at once (the easiest first)
+    pub multislot: bool,
+    /// Num of threads for the orientations and the PLL ways
+    pub threads: usize,
+}
+
+impl Default for CfopOptions {
+    fn default() -> Self {
+        CfopOptions {
+            neutral: None,
+            xcross: false,
+            xcross_depth: 6,
+            easiest_first: false,
+            multislot: false,
+            threads: 0,
+        }
+    }
+}
+
+/// F2L slots
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Slot {
+    FL,
+    FR,
+    BL,
+    BR,
+}
+
+impl Slot {
+    pub fn all() -> [Slot; 4] {
+        [Slot::FL, Slot::FR, Slot::BL, Slot::BR]
+    }
+    fn edge(&self) -> util::Edge {
+        let edges = util::edges();
+        match self {
+            Slot::FL => edges[4],
+            Slot::FR => edges[5],
+            Slot::BR => edges[6],
+            Slot::BL => edges[7],
+        }
+    }
+    fn corner(&self) -> util::Corner {
+        let corners = util::corners();
+        match self {
+            Slot::FL => corners[4],
+            Slot::FR => corners[5],
+            Slot::BR => corners[6],
+            Slot::BL => corners[7],
+        }
+    }
+}
+
+impl std::fmt::Display for Slot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
 pub fn cfop(cube: &Cube, verbose: bool) -> Option<Ops> {
+    cfop_with(cube, &CfopOptions::default(), verbose).map(|(alg, _)| alg)
+}
+
+/// CFOP from the orientation with the shortest cross (or the next ones if the rest fails),
+/// whose down face has one of `colors`
+pub fn cfop_neutral(cube: &Cube, colors: &[Color], verbose: bool) -> Option<Ops> {
+    let options = CfopOptions {
+        neutral: Some(colors.to_vec()),
+        ..CfopOptions::default()
+    };
+    cfop_with(cube, &options, verbose).map(|(alg, _)| alg)
+}
+
+/// CFOP with options.
+/// Returns the whole algorithm and the named steps.
+pub fn cfop_with(
+    cube: &Cube,
+    options: &CfopOptions,
+    verbose: bool,
+) -> Option<(Ops, Vec<(String, Ops)>)> {
+    let first_step = |c: &Cube| {
+        if options.xcross {
+            xcross(c, options.xcross_depth, verbose).or_else(|| cross(c, verbose))
+        } else {
+            cross(c, verbose)
+        }
+    };
+    match &options.neutral {
+        // The cross is the same up to y, and so is the rest
+        Some(colors) => neutral(cube, colors, options.threads, true, first_step)
+            .into_iter()
+            .find_map(|(rotation, cube, alg)| complete(&cube, rotation, alg, options, verbose)),
+        None => complete(cube, Ops::default(), first_step(cube)?, options, verbose),
+    }
+}
+
+/// The rest of CFOP after the cross (or X-cross) `alg` from the orientation by `rotation`
+fn complete(
+    cube: &Cube,
+    rotation: Ops,
+    alg: Ops,
+    options: &CfopOptions,
+    verbose: bool,
+) -> Option<(Ops, Vec<(String, Ops)>)> {
+    let mut steps = vec![];
+    if !rotation.is_empty() {
+        steps.push(("Rotation".to_string(), rotation));
+    }
     let mut cube = cube.clone();
+    let name = if !options.xcross || solved_slots(&alg.apply(&cube)).is_empty() {
+        "Cross"
+    } else {
+        "XCross"
+    };
+    cube = alg.apply(&cube);
+    steps.push((name.to_string(), alg));
+
+    for (name, alg) in f2l(&cube, options, verbose)? {
+        cube = alg.apply(&cube);
+        steps.push((name, alg));
+    }
+
+    info!("OLL");
+    let alg = oll(&cube, verbose)?;
+    cube = alg.apply(&cube);
+    steps.push(("OLL".to_string(), alg));
+
+    info!("PLL");
+    let alg = pll(&cube, options.threads, verbose)?;
+    cube = alg.apply(&cube);
+    let (pre, alg, post) = alg.trim_auf();
+    if !pre.is_empty() {
+        steps.push(("AUF".to_string(), pre));
+    }
+    steps.push(("PLL".to_string(), alg));
+    if !post.is_empty() {
+        steps.push(("AUF".to_string(), post));
+    }
+
+    info!("{}", cube);
+    // The rotations of the algorithms (e.g. z in Na perm) are pushed out of their steps;
+    // the rotation of color neutral stays the first step
+    let mut prefix = Ops::default();
     let mut algorithm = Ops::default();
+    for (name, alg) in steps.iter_mut() {
+        if name == "Rotation" {
+            prefix.extend(alg);
+            continue;
+        }
+        if alg.expand().data.iter().any(|op| op.is_rotation()) {
+            *alg = alg.simplify(&SimplifyOptions {
+                rotations: true,
+                slices: false,
+            });
+        }
+        algorithm.extend(alg);
+    }
+    let options = SimplifyOptions {
+        slices: true,
+        ..SimplifyOptions::default()
+    };
+    prefix.extend(&algorithm.simplify(&options));
+    Some((prefix, steps))
+}
 
+fn cross(cube: &Cube, verbose: bool) -> Option<Ops> {
     use Operation::*;
 
     info!("Cross");
@@ -38,30 +211,12 @@ pub fn cfop(cube: &Cube, verbose: bool) -> Option<Ops> {
         Left(true),
         Left(false),
     ];
-    match search_one(&subcube, &subgoal, allowed_ops, 5, verbose) {
-        Some(alg) => {
-            algorithm.extend(&alg);
-            cube = alg.apply(&cube);
-        }
-        None => {
-            return None;
-        }
-    }
+    search_one(&subcube, &subgoal, allowed_ops, 5, verbose)
+}
 
-    info!("F2L/#1");
-    let subgoal = cube![
-        . . . ;
-        . Y . ;
-        . . . ;
-        . . . . . . . . . . . . ;
-        R R . . G . . O . . B B ;
-        R R . . G . . O . . B B ;
-        W W . ;
-        W W W ;
-        . W . ;
-    ];
-    let mut subcube = cube.clone();
-    subcube.mask(&subgoal);
+/// Cross with the easiest pair
+fn xcross(cube: &Cube, max_depth: usize, verbose: bool) -> Option<Ops> {
+    use Operation::*;
     let allowed_ops = vec![
         Front(true),
         Front(false),
@@ -69,90 +224,184 @@ pub fn cfop(cube: &Cube, verbose: bool) -> Option<Ops> {
         Back(false),
         Up(true),
         Up(false),
+        Down(true),
+        Down(false),
         Right(true),
         Right(false),
         Left(true),
         Left(false),
     ];
-    match search_one(&subcube, &subgoal, allowed_ops, 6, verbose) {
-        Some(alg) => {
-            algorithm.extend(&alg);
-            cube = alg.apply(&cube);
-        }
-        None => {
-            return None;
-        }
-    }
-
-    info!("F2L/#2");
-    let subgoal = cube![
-        . . . ;
-        . Y . ;
-        . . . ;
-        . . . . . . . . . . . . ;
-        R R . . G . . O O B B B ;
-        R R . . G . . O O B B B ;
-        W W . ;
-        W W W ;
-        W W . ;
-    ];
-    let mut subcube = cube.clone();
-    subcube.mask(&subgoal);
-    let allowed_ops = vec![
-        Back(true),
-        Back(false),
-        Up(true),
-        Up(false),
-        Right(true),
-        Right(false),
-        Left(true),
-        Left(false),
-    ];
-    match search_one(&subcube, &subgoal, allowed_ops, 6, verbose) {
-        Some(alg) => {
-            algorithm.extend(&alg);
-            cube = alg.apply(&cube);
-        }
-        None => {
-            return None;
+    let mut ret: Option<Ops> = None;
+    for slot in Slot::all() {
+        info!("XCross/{}", slot);
+        let subgoal = f2l_goal(&[slot]);
+        let mut subcube = cube.clone();
+        subcube.mask(&subgoal);
+        if let Some(alg) = search_one(&subcube, &subgoal, allowed_ops.clone(), max_depth, verbose) {
+            if ret.as_ref().is_none_or(|best| alg.len() < best.len()) {
+                ret = Some(alg);
+            }
         }
     }
+    ret
+}
 
-    info!("F2L/#3+#4");
-    let subgoal = cube![
-        . . . ;
-        . Y . ;
-        . . . ;
-        . . . . . . . . . . . . ;
+/// Cross and the `slots` are solved, others are masked
+fn f2l_goal(slots: &[Slot]) -> Cube {
+    let solved = cube![
+        Y Y Y ;
+        Y Y Y ;
+        Y Y Y ;
+        R R R G G G O O O B B B ;
         R R R G G G O O O B B B ;
         R R R G G G O O O B B B ;
         W W W ;
         W W W ;
         W W W ;
     ];
-    let mut subcube = cube.clone();
-    subcube.mask(&subgoal);
-    let allowed_ops = vec![
-        Front(true),
-        Front(false),
-        Back(true),
-        Back(false),
-        Up(true),
-        Up(false),
-        Right(true),
-        Right(false),
+    let mut stickers = util::centers();
+    for (c, d) in util::edges() {
+        if matches!(c.0, FaceIndex::Down) || matches!(d.0, FaceIndex::Down) {
+            stickers.push(c);
+            stickers.push(d);
+        }
+    }
+    for slot in slots {
+        let (c, d) = slot.edge();
+        let (e, f, g) = slot.corner();
+        stickers.extend([c, d, e, f, g]);
+    }
+    let mut goal = solved.clone();
+    for c in util::centers() {
+        for i in 0..3 {
+            for j in 0..3 {
+                goal[c.0][(i, j)] = Color::Other;
+            }
+        }
+    }
+    for c in stickers {
+        goal[c.0][(c.1, c.2)] = solved[c.0].at(c.1, c.2);
+    }
+    goal
+}
+
+/// Slots already solved (with the cross)
+fn solved_slots(cube: &Cube) -> Vec<Slot> {
+    Slot::all()
+        .into_iter()
+        .filter(|&slot| {
+            let subgoal = f2l_goal(&[slot]);
+            let mut subcube = cube.clone();
+            subcube.mask(&subgoal);
+            subcube == subgoal
+        })
+        .collect()
+}
+
+fn slots_name(slots: &[Slot]) -> String {
+    slots
+        .iter()
+        .map(|slot| slot.to_string())
+        .collect::<Vec<_>>()
+        .join("+")
+}
+
+/// Solve the pairs: FL, BL, then FR and BR together,
+/// or the easiest slot first (`easiest_first`, or `multislot` to also try two at once)
+fn f2l(cube: &Cube, options: &CfopOptions, verbose: bool) -> Option<Vec<(String, Ops)>> {
+    if options.easiest_first || options.multislot {
+        f2l_easiest_first(cube, options.multislot, verbose)
+    } else {
+        f2l_in_order(cube, verbose)
+    }
+}
+
+fn f2l_ops(faces: &str) -> Vec<Operation> {
+    read::parse_ops(faces)
+        .unwrap()
+        .1
+        .data
+        .into_iter()
+        .flat_map(|op| [op.clone(), op.rev()])
+        .collect()
+}
+
+fn f2l_in_order(cube: &Cube, verbose: bool) -> Option<Vec<(String, Ops)>> {
+    let order = [
+        (vec![Slot::FL], "FBURL"),
+        (vec![Slot::BL], "BURL"),
+        (vec![Slot::FR, Slot::BR], "FBUR"),
     ];
-    match search_one(&subcube, &subgoal, allowed_ops, 6, verbose) {
-        Some(alg) => {
-            algorithm.extend(&alg);
-            cube = alg.apply(&cube);
+    let mut cube = cube.clone();
+    let mut steps = vec![];
+    for (slots, faces) in order {
+        let solved = solved_slots(&cube);
+        let slots: Vec<Slot> = slots
+            .into_iter()
+            .filter(|slot| !solved.contains(slot))
+            .collect();
+        if slots.is_empty() {
+            continue;
+        }
+        let name = format!("F2L/{}", slots_name(&slots));
+        info!("{}", name);
+        let subgoal = f2l_goal(&[solved, slots].concat());
+        let mut subcube = cube.clone();
+        subcube.mask(&subgoal);
+        let alg = search_one(&subcube, &subgoal, f2l_ops(faces), 6, verbose)?;
+        cube = alg.apply(&cube);
+        steps.push((name, alg));
+    }
+    Some(steps)
+}
+
+fn f2l_easiest_first(cube: &Cube, multislot: bool, verbose: bool) -> Option<Vec<(String, Ops)>> {
+    let allowed_ops = f2l_ops("FBURL");
+    let mut cube = cube.clone();
+    let mut steps = vec![];
+    loop {
+        let solved = solved_slots(&cube);
+        let remaining: Vec<Slot> = Slot::all()
+            .into_iter()
+            .filter(|slot| !solved.contains(slot))
+            .collect();
+        if remaining.is_empty() {
+            break;
         }
-        None => {
-            return None;
+        let mut candidates: Vec<Vec<Slot>> = remaining.iter().map(|&slot| vec![slot]).collect();
+        if multislot {
+            for i in 0..remaining.len() {
+                for j in i + 1..remaining.len() {
+                    candidates.push(vec![remaining[i], remaining[j]]);
+                }
+            }
+        }
+        // (slots, algorithm) with the fewest moves per slot
+        let mut best: Option<(Vec<Slot>, Ops)> = None;
+        for slots in candidates {
+            info!("F2L/{}", slots_name(&slots));
+            let subgoal = f2l_goal(&[solved.clone(), slots.clone()].concat());
+            let mut subcube = cube.clone();
+            subcube.mask(&subgoal);
+            if let Some(alg) = search_one(&subcube, &subgoal, allowed_ops.clone(), 6, verbose) {
+                if best.as_ref().is_none_or(|(best_slots, best_alg)| {
+                    alg.len() * best_slots.len() < best_alg.len() * slots.len()
+                }) {
+                    best = Some((slots, alg));
+                }
+            }
         }
+        let (slots, alg) = best?;
+        let name = format!("F2L/{}", slots_name(&slots));
+        cube = alg.apply(&cube);
+        steps.push((name, alg));
     }
+    Some(steps)
+}
+
+fn oll(cube: &Cube, verbose: bool) -> Option<Ops> {
+    use Operation::*;
 
-    info!("OLL");
     let mut subcube = cube.clone();
     for i in 0..3 {
         for j in 0..3 {
@@ -186,55 +435,40 @@ pub fn cfop(cube: &Cube, verbose: bool) -> Option<Ops> {
     ];
     info!("OLL/UR");
     let allowed_ops = vec![Right(true), Right(false), Up(true), Up(false)];
-    match search_one(&subcube, &subgoal, allowed_ops, 8, verbose) {
-        Some(alg) => {
-            algorithm.extend(&alg);
-            cube = alg.apply(&cube);
-        }
-        None => {
-            info!("OLL/FUR");
-            let allowed_ops = vec![
-                Right(true),
-                Right(false),
-                Up(true),
-                Up(false),
-                Front(true),
-                Front(false),
-            ];
-            match search_one(&subcube, &subgoal, allowed_ops, 8, verbose) {
-                Some(alg) => {
-                    algorithm.extend(&alg);
-                    cube = alg.apply(&cube);
-                }
-                None => {
-                    info!("OLL/Full");
-                    let allowed_ops = vec![
-                        Front(true),
-                        Front(false),
-                        Up(true),
-                        Up(false),
-                        Right(true),
-                        Right(false),
-                        RightDouble(true),
-                        RightDouble(false),
-                        // Left(true),
-                        // Left(false),
-                    ];
-                    match search_one(&subcube, &subgoal, allowed_ops, 8, verbose) {
-                        Some(alg) => {
-                            algorithm.extend(&alg);
-                            cube = alg.apply(&cube);
-                        }
-                        None => {
-                            return None;
-                        }
-                    }
-                }
-            }
-        }
+    if let Some(alg) = search_one(&subcube, &subgoal, allowed_ops, 8, verbose) {
+        return Some(alg);
     }
+    info!("OLL/FUR");
+    let allowed_ops = vec![
+        Right(true),
+        Right(false),
+        Up(true),
+        Up(false),
+        Front(true),
+        Front(false),
+    ];
+    if let Some(alg) = search_one(&subcube, &subgoal, allowed_ops, 8, verbose) {
+        return Some(alg);
+    }
+    info!("OLL/Full");
+    let allowed_ops = vec![
+        Front(true),
+        Front(false),
+        Up(true),
+        Up(false),
+        Right(true),
+        Right(false),
+        RightDouble(true),
+        RightDouble(false),
+        // Left(true),
+        // Left(false),
+    ];
+    search_one(&subcube, &subgoal, allowed_ops, 8, verbose)
+}
+
+fn pll(cube: &Cube, threads: usize, verbose: bool) -> Option<Ops> {
+    use Operation::*;
 
-    info!("PLL");
     let subgoal = cube![
         Y Y Y ;
         Y Y Y ;
@@ -249,27 +483,27 @@ pub fn cfop(cube: &Cube, verbose: bool) -> Option<Ops> {
     let sexy = Operation::Compound(
         "Sx".to_string(),
         true,
-        read::parse_ops(&"RUR'U'").unwrap().1.data,
+        read::parse_ops("RUR'U'").unwrap().1.data,
     );
     let sledgehammer = Operation::Compound(
         "Sh".to_string(),
         true,
-        read::parse_ops(&"R'FRF'").unwrap().1.data,
+        read::parse_ops("R'FRF'").unwrap().1.data,
     );
     let jb = Operation::Compound(
         "Jb".to_string(),
         true,
-        read::parse_ops(&"RUR'F' RUR'U' R'FR2U'R'").unwrap().1.data,
+        read::parse_ops("RUR'F' RUR'U' R'FR2U'R'").unwrap().1.data,
     );
     let ja = Operation::Compound(
         "Ja".to_string(),
         true,
-        read::parse_ops(&"r2FrU2 R'FRU F'r").unwrap().1.data,
+        read::parse_ops("r2FrU2 R'FRU F'r").unwrap().1.data,
     );
     let e = Operation::Compound(
         "E".to_string(),
         true,
-        read::parse_ops(&"R B' R' F R B R' F' R B R' F R B' R' F'")
+        read::parse_ops("R B' R' F R B R' F' R B R' F R B' R' F'")
             .unwrap()
             .1
             .data,
@@ -277,7 +511,7 @@ pub fn cfop(cube: &Cube, verbose: bool) -> Option<Ops> {
     let f = Operation::Compound(
         "F".to_string(),
         true,
-        read::parse_ops(&"R'U'F'R UR'U'R' FR2U'R' U'RUR'UR")
+        read::parse_ops("R'U'F'R UR'U'R' FR2U'R' U'RUR'UR")
             .unwrap()
             .1
             .data,
@@ -285,7 +519,7 @@ pub fn cfop(cube: &Cube, verbose: bool) -> Option<Ops> {
     let v = Operation::Compound(
         "V".to_string(),
         true,
-        read::parse_ops(&"F'UF'U' R'F'R2 U'R'UR' FRF")
+        read::parse_ops("F'UF'U' R'F'R2 U'R'UR' FRF")
             .unwrap()
             .1
             .data,
@@ -293,7 +527,7 @@ pub fn cfop(cube: &Cube, verbose: bool) -> Option<Ops> {
     let gb = Operation::Compound(
         "Gb".to_string(),
         true,
-        read::parse_ops(&"R' U' R U D' R2 U R' U R U' R U' R2 D")
+        read::parse_ops("R' U' R U D' R2 U R' U R U' R U' R2 D")
             .unwrap()
             .1
             .data,
@@ -301,7 +535,7 @@ pub fn cfop(cube: &Cube, verbose: bool) -> Option<Ops> {
     let gd = Operation::Compound(
         "Gd".to_string(),
         true,
-        read::parse_ops(&"R U R' U' D R2 U' R U' R' U R' U R2 D'")
+        read::parse_ops("R U R' U' D R2 U' R U' R' U R' U R2 D'")
             .unwrap()
             .1
             .data,
@@ -309,7 +543,7 @@ pub fn cfop(cube: &Cube, verbose: bool) -> Option<Ops> {
     let na = Operation::Compound(
         "Na".to_string(),
         true,
-        read::parse_ops(&"z U R' D R2 U' R D' U R' D R2 U' R D' z'")
+        read::parse_ops("z U R' D R2 U' R D' U R' D R2 U' R D' z'")
             .unwrap()
             .1
             .data,
@@ -317,7 +551,7 @@ pub fn cfop(cube: &Cube, verbose: bool) -> Option<Ops> {
     let nb = Operation::Compound(
         "Nb".to_string(),
         true,
-        read::parse_ops(&"R' U R U' R' F' U' F R U R' F R' F' R U' R")
+        read::parse_ops("R' U R U' R' F' U' F R U R' F R' F' R U' R")
             .unwrap()
             .1
             .data,
@@ -325,7 +559,7 @@ pub fn cfop(cube: &Cube, verbose: bool) -> Option<Ops> {
     let aa = Operation::Compound(
         "Aa".to_string(),
         true,
-        read::parse_ops(&"x L2 D2 L' U' L D2 L' U L' x'")
+        read::parse_ops("x L2 D2 L' U' L D2 L' U L' x'")
             .unwrap()
             .1
             .data,
@@ -333,7 +567,7 @@ pub fn cfop(cube: &Cube, verbose: bool) -> Option<Ops> {
     let ra = Operation::Compound(
         "Ra".to_string(),
         true,
-        read::parse_ops(&"R U' R' U' R U R D R' U' R D' R' U2 R'")
+        read::parse_ops("R U' R' U' R U R D R' U' R D' R' U2 R'")
             .unwrap()
             .1
             .data,
@@ -341,7 +575,7 @@ pub fn cfop(cube: &Cube, verbose: bool) -> Option<Ops> {
     let rb = Operation::Compound(
         "Rb".to_string(),
         true,
-        read::parse_ops(&"R2 F R U R U' R' F' R U2 R' U2 R")
+        read::parse_ops("R2 F R U R U' R' F' R U2 R' U2 R")
             .unwrap()
             .1
             .data,
@@ -372,16 +606,64 @@ pub fn cfop(cube: &Cube, verbose: bool) -> Option<Ops> {
             7,
         ),
     ];
-    match search_any(&cube, &subgoal, ways, verbose, 23) {
-        Some(alg) => {
-            algorithm.extend(&alg);
-            cube = alg.apply(&cube);
-        }
-        None => {
-            return None;
-        }
+    // The AUFs before and after are free in the search
+    let options = SearchOptions {
+        threads,
+        verbose,
+        pre_auf: true,
+        post_auf: true,
+        ..SearchOptions::default()
+    };
+    search_any_with(cube, &subgoal, ways, 23, &options)
+}
+
+#[cfg(test)]
+mod test_cfop {
+    use crate::entities::*;
+    use crate::read;
+    use crate::solver::cfop::xcross;
+    use crate::solver::{cfop_with, CfopOptions};
+
+    #[test]
+    fn test_cfop_steps() {
+        let (cube, goal) = read::read("Scramble { U R U' R' }");
+        let (alg, steps) = cfop_with(&cube, &CfopOptions::default(), false).unwrap();
+        assert_eq!(alg.apply(&cube), goal);
+        let names: Vec<&str> = steps.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["Cross", "F2L/FR", "OLL", "PLL", "AUF"]);
     }
 
-    info!("{}", cube);
-    Some(algorithm.expand().shorten())
+    #[test]
+    fn test_xcross() {
+        let (cube, goal) = read::read("Scramble { D R U R' D' }");
+        let options = CfopOptions {
+            xcross: true,
+            multislot: true,
+            ..CfopOptions::default()
+        };
+        let (alg, steps) = cfop_with(&cube, &options, false).unwrap();
+        assert_eq!(alg.apply(&cube), goal);
+        assert_eq!(steps[0].0, "XCross");
+        // The depth is taken from the options (from each side)
+        let (cube, _) = read::read("Scramble { F D2 L B U' R }");
+        let n = xcross(&cube, options.xcross_depth, false).unwrap().len();
+        assert!(xcross(&cube, (n - 1) / 2, false).is_none());
+    }
+
+    #[test]
+    fn test_neutral_rotation() {
+        let (cube, _) = read::read("Scramble { z2 R U }");
+        let options = CfopOptions {
+            neutral: Some(vec![Color::White]),
+            ..CfopOptions::default()
+        };
+        let (alg, steps) = cfop_with(&cube, &options, false).unwrap();
+        assert_eq!(alg.apply(&cube).down, Face::new([[Color::White; 3]; 3]));
+        assert_eq!(steps[0].0, "Rotation");
+        let mut sum = Ops::default();
+        for (_, step) in steps.iter() {
+            sum.extend(step);
+        }
+        assert_eq!(format!("{}", alg), format!("{}", sum));
+    }
 }
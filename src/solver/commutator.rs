@@ -0,0 +1,334 @@
+use crate::entities::*;
+use crate::solver::parallel;
+use crate::solver::search::{search_with, SearchOptions};
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use Operation::*;
+
+/// Num of the solutions of the search for each setup, filtered to the commutators
+const SOLUTIONS_PER_SETUP: usize = 50;
+/// The commutators up to this many moves longer than the shortest are searched for
+const SLACK: usize = 2;
+
+#[derive(Debug, Clone)]
+pub struct CommutatorOptions {
+    /// Max length of the pure commutator `[B, C]`
+    pub max_depth: usize,
+    /// Max num of the turns (e.g. R, R2, R') of the setup `A`
+    pub setup_depth: usize,
+    /// Max num of the commutators
+    pub num: usize,
+    /// Empty for the faces (and M, E, S for the edges)
+    pub allowed_ops: Vec<Operation>,
+    pub threads: usize,
+}
+
+impl Default for CommutatorOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: 10,
+            setup_depth: 1,
+            num: 10,
+            allowed_ops: vec![],
+            threads: 1,
+        }
+    }
+}
+
+/// `[A: [B, C]]`, which is `A B C B' C' A'`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Commutator {
+    pub setup: Ops,
+    pub first: Ops,
+    pub second: Ops,
+    /// `B C B' C'` as found
+    pub pure: Ops,
+}
+
+impl Commutator {
+    pub fn algorithm(&self) -> Ops {
+        let mut ops = self.setup.clone();
+        ops.extend(&self.pure);
+        ops.extend(&self.setup.rev());
+        ops
+    }
+
+    /// Cost of the finger tricks by the default model
+    pub fn ergonomics(&self) -> usize {
+        self.algorithm().cost(&Ergonomics::default())
+    }
+}
+
+impl std::fmt::Display for Commutator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.setup.is_empty() {
+            write!(f, "[{}, {}]", self.first, self.second)
+        } else {
+            write!(f, "[{}: [{}, {}]]", self.setup, self.first, self.second)
+        }
+    }
+}
+
+/// The piece at the sticker `a` goes to `b`, `b` to `c`, and `c` to `a`.
+/// None unless they are of three different pieces of the same kind.
+fn three_cycle(a: usize, b: usize, c: usize) -> Option<Permutation> {
+    let (pa, ia) = Piece::of_sticker(a)?;
+    let (pb, ib) = Piece::of_sticker(b)?;
+    let (pc, ic) = Piece::of_sticker(c)?;
+    if pa.is_corner() != pb.is_corner() || pa.is_corner() != pc.is_corner() {
+        return None;
+    }
+    if pa == pb || pb == pc || pc == pa {
+        return None;
+    }
+    let (sa, sb, sc) = (pa.stickers(), pb.stickers(), pc.stickers());
+    let n = sa.len();
+    let mut sources = *Permutation::identity().sources();
+    for k in 0..n {
+        let (x, y, z) = (sa[(ia + k) % n], sb[(ib + k) % n], sc[(ic + k) % n]);
+        sources[y] = x as u8;
+        sources[z] = y as u8;
+        sources[x] = z as u8;
+    }
+    Permutation::from_sources(&sources)
+}
+
+/// Setups up to `depth` turns, with the 3-cycle seen through each of them
+fn setups(target: &Permutation, ops: &[Operation], depth: usize) -> Vec<(Ops, Permutation)> {
+    let faces: Vec<&Operation> = ops.iter().filter(|op| !op.is_reversed()).collect();
+    let mut setups = vec![(Ops::default(), *target)];
+    let mut seen = BTreeSet::new();
+    seen.insert(*target.sources());
+    let mut layer = vec![Ops::default()];
+    for _ in 0..depth {
+        let mut next = vec![];
+        for setup in layer.iter() {
+            for &op in faces.iter() {
+                if setup.last().is_some_and(|last| last.axis() == op.axis()) {
+                    continue;
+                }
+                for turn in [
+                    vec![op.clone()],
+                    vec![op.clone(), op.clone()],
+                    vec![op.rev()],
+                ] {
+                    let mut setup = setup.clone();
+                    for op in turn {
+                        setup.push(op);
+                    }
+                    // [A: P] is the target if P is A' target A
+                    let pure = Permutation::of_ops(&setup).conjugate(target);
+                    if seen.insert(*pure.sources()) {
+                        setups.push((setup.clone(), pure));
+                    }
+                    next.push(setup);
+                }
+            }
+        }
+        layer = next;
+    }
+    setups
+}
+
+/// The sequences with the commuting moves in a row reordered (e.g. U D R -> U D R, D U R)
+fn orders(ops: &Ops) -> Vec<Ops> {
+    let mut orders = vec![Ops::default()];
+    let mut i = 0;
+    while i < ops.len() {
+        let axis = ops.data[i].axis();
+        let mut j = i + 1;
+        while j < ops.len() && axis.is_some() && ops.data[j].axis() == axis {
+            j += 1;
+        }
+        let mut runs = BTreeSet::new();
+        permutations(
+            &ops.data[i..j],
+            &mut vec![],
+            &mut vec![false; j - i],
+            &mut runs,
+        );
+        orders = orders
+            .iter()
+            .flat_map(|order| {
+                runs.iter().map(move |run: &Vec<Operation>| {
+                    let mut order = order.clone();
+                    order.extend(&Ops::new(run.clone()));
+                    order
+                })
+            })
+            .collect();
+        i = j;
+    }
+    orders
+}
+
+/// The distinct permutations of `ops`
+fn permutations(
+    ops: &[Operation],
+    run: &mut Vec<Operation>,
+    used: &mut Vec<bool>,
+    runs: &mut BTreeSet<Vec<Operation>>,
+) {
+    if run.len() == ops.len() {
+        runs.insert(run.clone());
+        return;
+    }
+    for k in 0..ops.len() {
+        if !used[k] {
+            used[k] = true;
+            run.push(ops[k].clone());
+            permutations(ops, run, used, runs);
+            run.pop();
+            used[k] = false;
+        }
+    }
+}
+
+/// 3-style commutators which move the piece at the sticker `buffer` to `first`,
+/// at `first` to `second`, and at `second` to `buffer`.
+/// The pure commutators are searched for each setup with the allowed moves,
+/// and ranked by the length and the ergonomics.
+/// None if the stickers are not of three pieces of the same kind.
+pub fn commutators(
+    buffer: usize,
+    first: usize,
+    second: usize,
+    options: &CommutatorOptions,
+) -> Option<Vec<Commutator>> {
+    let target = three_cycle(buffer, first, second)?;
+    let allowed_ops = if options.allowed_ops.is_empty() {
+        let mut ops = vec![
+            Up(true),
+            Down(true),
+            Front(true),
+            Back(true),
+            Left(true),
+            Right(true),
+        ];
+        if !Piece::of_sticker(buffer)?.0.is_corner() {
+            ops.extend([Middle(true), Equator(true), Standing(true)]);
+        }
+        ops.iter().flat_map(|op| [op.clone(), op.rev()]).collect()
+    } else {
+        options.allowed_ops.clone()
+    };
+    let solved = Cube::solved();
+    let setups = setups(&target, &allowed_ops, options.setup_depth);
+    // The shortest commutator so far, to search the other setups shallower
+    let shortest = AtomicUsize::new(usize::MAX);
+    let search = |(setup, pure): &(Ops, Permutation)| {
+        let limit = shortest.load(Ordering::Relaxed).saturating_add(SLACK);
+        let max_length = options.max_depth.min(limit.saturating_sub(2 * setup.len()));
+        if max_length < 2 {
+            return vec![];
+        }
+        let mut goal = solved.clone();
+        goal.set_stickers(&pure.permute(&solved.stickers()));
+        // Both of the sides go to the depth
+        let search_options = SearchOptions::new(max_length.div_ceil(2), SOLUTIONS_PER_SETUP);
+        let result = search_with(&solved, &goal, allowed_ops.clone(), &search_options);
+        let found: Vec<Commutator> = result
+            .solutions
+            .into_iter()
+            .filter(|ops| ops.len() <= max_length)
+            .filter_map(|ops| {
+                // The search keeps one order of the commuting moves (e.g. U D but not D U)
+                let (ops, (first, second)) = orders(&ops)
+                    .into_iter()
+                    .find_map(|ops| ops.commutator().map(|comm| (ops, comm)))?;
+                // The setup would be merged into the commutator
+                let axis = setup.last().and_then(|op| op.axis());
+                let ends = [ops.data.first(), ops.data.last()];
+                if axis.is_some() && ends.iter().flatten().any(|op| op.axis() == axis) {
+                    return None;
+                }
+                Some(Commutator {
+                    setup: setup.clone(),
+                    first,
+                    second,
+                    pure: ops,
+                })
+            })
+            .collect();
+        if let Some(length) = found.iter().map(|comm| comm.algorithm().len()).min() {
+            shortest.fetch_min(length, Ordering::Relaxed);
+        }
+        found
+    };
+    // Without the setup first (the setups are sorted by the length)
+    let mut found = vec![search(&setups[0])];
+    found.extend(parallel::map(&setups[1..], options.threads, search));
+    let mut seen = BTreeSet::new();
+    let mut commutators: Vec<Commutator> = found
+        .into_iter()
+        .flatten()
+        .filter(|comm| {
+            // e.g. [M, U2] by UU and U'U'
+            let key = [&comm.setup, &comm.first, &comm.second]
+                .map(|ops| *Permutation::of_ops(ops).sources());
+            seen.insert(key)
+        })
+        .collect();
+    commutators.sort_by_cached_key(|comm| (comm.algorithm().len(), comm.ergonomics()));
+    commutators.truncate(options.num);
+    Some(commutators)
+}
+
+#[cfg(test)]
+mod test_commutator {
+    use crate::solver::commutator::*;
+
+    fn sticker(name: &str) -> usize {
+        Piece::sticker_named(name).unwrap()
+    }
+
+    #[test]
+    fn test_commutators() {
+        let ops = |ops: &[Operation]| ops.iter().flat_map(|op| [op.clone(), op.rev()]).collect();
+        for (buffer, first, second, allowed_ops, best) in [
+            (
+                "UFR",
+                "UBL",
+                "RDF",
+                ops(&[Up(true), Down(true), Right(true)]),
+                "[R'D'R, UU]",
+            ),
+            ("UF", "UB", "DF", ops(&[Up(true), Middle(true)]), "[UU, M']"),
+        ] {
+            let options = CommutatorOptions {
+                num: 3,
+                allowed_ops,
+                ..CommutatorOptions::default()
+            };
+            let (a, b, c) = (sticker(buffer), sticker(first), sticker(second));
+            let target = three_cycle(a, b, c).unwrap();
+            assert_eq!(target.source(b), a);
+            let comms = commutators(a, b, c, &options).unwrap();
+            assert_eq!(comms[0].to_string(), best);
+            for comm in comms.iter() {
+                assert_eq!(Permutation::of_ops(&comm.algorithm()), target);
+                assert!(comm.pure.is_pure_commutator());
+                // Only with the allowed moves
+                let algorithm = comm.algorithm();
+                assert!(algorithm
+                    .data
+                    .iter()
+                    .all(|op| options.allowed_ops.contains(op)));
+            }
+        }
+        // <R,U,D> with a setup
+        let options = CommutatorOptions {
+            num: 3,
+            allowed_ops: ops(&[Right(true), Up(true), Down(true)]),
+            ..CommutatorOptions::default()
+        };
+        let comms = commutators(sticker("UFR"), sticker("UBL"), sticker("RDF"), &options).unwrap();
+        assert_eq!(comms.len(), 3);
+        assert_eq!(comms[1].to_string(), "[R': [D', U'RU'R']]");
+
+        let options = CommutatorOptions::default();
+        // Not a 3-cycle
+        assert!(commutators(sticker("UF"), sticker("FU"), sticker("UB"), &options).is_none());
+        assert!(commutators(sticker("UF"), sticker("UBR"), sticker("UB"), &options).is_none());
+    }
+}
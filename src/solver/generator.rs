@@ -0,0 +1,307 @@
+use crate::entities::*;
+use crate::solver::budget::{Budget, Meter};
+use crate::solver::parallel;
+use crate::solver::search::SearchOptions;
+use log::info;
+
+#[derive(Debug, Clone)]
+pub struct GeneratorOptions {
+    /// Max num of the moves (a half turn is two)
+    pub max_depth: usize,
+    /// Max length of the algorithms in the metric
+    pub max_length: Option<usize>,
+    /// Max num of the algorithms
+    pub num: usize,
+    /// The algorithms are sorted by the length in the metric
+    pub metric: Metric,
+    /// Or by the cost of the finger tricks
+    pub ergonomics: Option<Ergonomics>,
+    /// The algorithms with the equivalent effects are deduped (identical by default).
+    /// Without wildcards in the goal, the effects are all the same, so only the same moves are.
+    pub equivalence: EquivalenceOptions,
+    /// Constraints on the moves (as `SearchOptions::constraints`)
+    pub constraints: Constraints,
+    /// For each move set
+    pub budget: Budget,
+    pub threads: usize,
+}
+
+impl Default for GeneratorOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: 8,
+            max_length: None,
+            num: 100,
+            metric: Metric::Htm,
+            ergonomics: None,
+            equivalence: EquivalenceOptions::default(),
+            constraints: Constraints::default(),
+            budget: Budget::default(),
+            threads: 1,
+        }
+    }
+}
+
+/// The effects of the turns (e.g. UU and U'U' are the same)
+fn turns(ops: &Ops) -> Vec<Permutation> {
+    let ops = ops.expand().shorten();
+    let mut turns: Vec<Ops> = vec![];
+    for op in ops.data {
+        match turns.last_mut() {
+            Some(turn) if turn.last().as_ref() == Some(&op) => turn.push(op),
+            _ => turns.push(Ops::new(vec![op])),
+        }
+    }
+    turns.iter().map(Permutation::of_ops).collect()
+}
+
+/// Depth-first enumeration of all the algorithms up to the max depth.
+/// Only the redundant sequences are pruned (as in the search tree), not the transpositions.
+struct Enumerator<'a> {
+    ops: &'a [Operation],
+    perms: Vec<Permutation>,
+    goals: Vec<PackedCube>,
+    /// Prune one order of the commuting moves (not with the avoided turns)
+    ordered: bool,
+    constraints: &'a Constraints,
+    meter: Meter,
+    ticks: usize,
+    path: Vec<usize>,
+    found: Vec<Ops>,
+}
+
+impl Enumerator<'_> {
+    fn redundant(&self, k: usize) -> bool {
+        let op = &self.ops[k];
+        let n = self.path.len();
+        let Some(&last) = self.path.last() else {
+            return false;
+        };
+        let last = &self.ops[last];
+        // Canceling (UU'), U'U' (as UU), UUU (as U') and the other order of UD
+        *last == op.rev()
+            || (last == op && op.is_reversed())
+            || (n >= 2 && self.path[n - 2] == k && last == op)
+            || (self.ordered && last.is_unordered(op))
+    }
+
+    fn ops(&self) -> Ops {
+        Ops::new(self.path.iter().map(|&k| self.ops[k].clone()).collect())
+    }
+
+    /// Returns false when the budget is exhausted
+    fn visit(&mut self, cube: PackedCube, depth: usize) -> bool {
+        if !self.meter.tick(&mut self.ticks) {
+            return false;
+        }
+        if !self.path.is_empty() && self.goals.iter().any(|goal| cube.matched(goal)) {
+            let ops = self.ops();
+            self.found.push(ops);
+        }
+        if depth == 0 {
+            return true;
+        }
+        for k in 0..self.ops.len() {
+            if self.redundant(k) {
+                continue;
+            }
+            self.path.push(k);
+            let fits =
+                !self.constraints.restricts_moves() || self.constraints.allows_part(&self.ops());
+            if fits && !self.visit(cube.permute(&self.perms[k]), depth - 1) {
+                return false;
+            }
+            self.path.pop();
+        }
+        true
+    }
+}
+
+/// All the algorithms up to `max_depth` moves (within the prefix and the suffix),
+/// or the ones found within the budget
+fn enumerate(
+    init_state: &Cube,
+    goal: &Cube,
+    allowed_ops: &[Operation],
+    options: &GeneratorOptions,
+) -> Vec<Ops> {
+    let constraints = &options.constraints;
+    let search_options = SearchOptions {
+        constraints: constraints.clone(),
+        ..SearchOptions::default()
+    };
+    let (inits, goals) = search_options.ends(init_state, std::slice::from_ref(goal));
+    let mut enumerator = Enumerator {
+        ops: allowed_ops,
+        perms: allowed_ops.iter().map(Permutation::of).collect(),
+        goals: goals.iter().map(PackedCube::pack).collect(),
+        ordered: !constraints.restricts_moves(),
+        constraints,
+        meter: options.budget.start(),
+        ticks: 0,
+        path: vec![],
+        found: vec![],
+    };
+    for init in inits.iter() {
+        if !enumerator.visit(PackedCube::pack(init), options.max_depth) {
+            info!("Generation is cut off ({} nodes)", enumerator.meter.nodes());
+            break;
+        }
+    }
+    enumerator
+        .found
+        .into_iter()
+        .map(|ops| {
+            let mut alg = constraints.prefix.clone();
+            alg.extend(&ops);
+            alg.extend(&constraints.suffix);
+            alg
+        })
+        .filter(|alg| constraints.allows(alg))
+        .collect()
+}
+
+/// Enumerate the algorithms from `init_state` to `goal` with each move set.
+/// The algorithms are deduped by the effects (or by the moves, cancelled, for a goal
+/// without wildcards), keeping the best, and sorted by the cost if ergonomic, the
+/// length in the metric, and then the num of the moves.
+pub fn generate(
+    init_state: &Cube,
+    goal: &Cube,
+    move_sets: &[Vec<Operation>],
+    options: &GeneratorOptions,
+) -> Vec<Ops> {
+    let found = parallel::map(move_sets, options.threads, |allowed_ops| {
+        enumerate(init_state, goal, allowed_ops, options)
+    });
+    let score = |ops: &Ops| {
+        let cost = options.ergonomics.as_ref().map(|e| ops.cost(e));
+        (cost, ops.length_in(options.metric), ops.len())
+    };
+    let mut algs: Vec<Ops> = found
+        .into_iter()
+        .flatten()
+        .filter(|ops| {
+            options
+                .max_length
+                .is_none_or(|max_length| ops.length_in(options.metric) <= max_length)
+        })
+        .collect();
+    algs.sort_by_key(score);
+    let by_effect = goal.has_wildcard();
+    let mut generated: Vec<Ops> = vec![];
+    for alg in algs {
+        if generated.len() >= options.num {
+            break;
+        }
+        let duplicated = generated.iter().any(|other| {
+            if by_effect {
+                alg.equivalent(other, &options.equivalence).is_some()
+            } else {
+                turns(&alg) == turns(other)
+            }
+        });
+        if !duplicated {
+            generated.push(alg);
+        }
+    }
+    generated
+}
+
+#[cfg(test)]
+mod test_generator {
+    use crate::read;
+    use crate::solver::generator::*;
+    use std::collections::BTreeSet;
+    use Operation::*;
+
+    #[test]
+    fn test_generate() {
+        // Sune case: only the U colors of the corners of U are checked
+        let init = read::parse_ops("R U2 R' U' R U' R'")
+            .unwrap()
+            .1
+            .apply(&Cube::solved());
+        let mut goal = Cube::solved();
+        let mut stickers = goal.stickers();
+        for piece in Piece::all() {
+            let ll = piece.name().contains('U');
+            for (i, &k) in piece.stickers().iter().enumerate() {
+                if ll && (!piece.is_corner() || i > 0) {
+                    stickers[k] = Color::Wildcard;
+                }
+            }
+        }
+        goal.set_stickers(&stickers);
+        let ru = vec![Up(true), Up(false), Right(true), Right(false)];
+        let mut ruf = ru.clone();
+        ruf.extend([Front(true), Front(false)]);
+        let options = GeneratorOptions {
+            max_depth: 9,
+            max_length: Some(8),
+            num: usize::MAX,
+            ..GeneratorOptions::default()
+        };
+        let algs = generate(&init, &goal, &[ru.clone(), ruf.clone()], &options);
+        for (i, alg) in algs.iter().enumerate() {
+            assert!(alg.apply(&init).matched(&goal));
+            assert!(alg.length_in(Metric::Htm) <= 8);
+            for other in algs[..i].iter() {
+                assert_ne!(Permutation::of_ops(alg), Permutation::of_ops(other));
+                assert!(other.length_in(Metric::Htm) <= alg.length_in(Metric::Htm));
+            }
+        }
+        assert_eq!(algs[0].length_in(Metric::Htm), 7);
+
+        // All the effects of <R, U> up to 8 moves, by brute force
+        let mut effects = BTreeSet::new();
+        let mut layer = vec![Ops::default()];
+        for _ in 0..8 {
+            let mut next = vec![];
+            for ops in layer.iter() {
+                for op in ru.iter() {
+                    let mut ops = ops.clone();
+                    ops.push(op.clone());
+                    if ops.apply(&init).matched(&goal) {
+                        effects.insert(ops.apply(&Cube::solved()));
+                    }
+                    next.push(ops);
+                }
+            }
+            layer = next;
+        }
+        let brute = GeneratorOptions {
+            max_depth: 8,
+            max_length: None,
+            ..options.clone()
+        };
+        let generated = generate(&init, &goal, std::slice::from_ref(&ru), &brute);
+        assert_eq!(generated.len(), effects.len());
+
+        // Modulo AUF (e.g. the Sune and then U)
+        let algs = generate(&init, &goal, std::slice::from_ref(&ru), &options);
+        let options = GeneratorOptions {
+            equivalence: EquivalenceOptions {
+                auf: true,
+                ..EquivalenceOptions::default()
+            },
+            ..options
+        };
+        let fewer = generate(&init, &goal, std::slice::from_ref(&ru), &options);
+        assert!(fewer.len() < algs.len());
+
+        // Without wildcards, only the same moves are deduped
+        let goal = Cube::solved();
+        let mut ru_m = ru.clone();
+        ru_m.extend([Middle(true), Middle(false)]);
+        let init = read::parse_ops("M2 U M U2 M' U M2").unwrap().1.apply(&goal);
+        let options = GeneratorOptions {
+            max_depth: 10,
+            max_length: None,
+            ..GeneratorOptions::default()
+        };
+        let algs = generate(&init, &goal, &[ru, ru_m], &options);
+        assert_eq!(format!("{}", algs[0]), "MMU'MUUM'U'MM");
+        assert!(algs.iter().all(|alg| alg.apply(&init) == goal));
+    }
+}
@@ -0,0 +1,655 @@
+use crate::entities::*;
+use crate::solver::SearchOptions;
+use crate::util;
+use log::{info, warn};
+
+/// Num of stickers used as the key of the index
+const KEY_LEN: usize = 10;
+/// Num of Color variants
+const NUM_COLORS: usize = 8;
+
+enum Node {
+    Branch([u32; NUM_COLORS]),
+    Leaf(Vec<PackedCube>),
+}
+
+/// Trie over some stickers, to find a cube matched with wildcards.
+/// A Wildcard sticker (in the stored cubes or in the query) follows every branch,
+/// and the rest of the stickers are checked with `PackedCube::matched` in the leaves.
+pub(crate) struct Index {
+    stickers: Vec<usize>,
+    nodes: Vec<Node>,
+}
+
+impl Index {
+    /// Stickers concrete in the patterns (init and goal) are used first,
+    /// as they split the cubes well
+    pub fn new(patterns: &[&Cube]) -> Self {
+        let mut positions = vec![];
+        for (c, d, e) in util::corners() {
+            positions.extend([c, d, e]);
+        }
+        for (c, d) in util::edges() {
+            positions.extend([c, d]);
+        }
+        positions.sort_by_key(|p| {
+            patterns
+                .iter()
+                .map(|pattern| match pattern[p.0].at(p.1, p.2) {
+                    Color::Wildcard => 2,
+                    Color::Other => 1,
+                    _ => 0,
+                })
+                .sum::<usize>()
+        });
+        let stickers = positions
+            .into_iter()
+            .take(KEY_LEN)
+            .map(|(face, i, j)| Cube::sticker(face, i, j))
+            .collect();
+        Self {
+            stickers,
+            nodes: vec![Node::Branch([0; NUM_COLORS])],
+        }
+    }
+
+    pub fn insert(&mut self, cube: PackedCube) {
+        let mut k = 0;
+        for depth in 0..self.stickers.len() {
+            let col = self.color(&cube, depth);
+            let Node::Branch(children) = &self.nodes[k] else {
+                unreachable!()
+            };
+            k = if children[col] > 0 {
+                children[col] as usize
+            } else {
+                let child = self.nodes.len();
+                if depth + 1 < self.stickers.len() {
+                    self.nodes.push(Node::Branch([0; NUM_COLORS]));
+                } else {
+                    self.nodes.push(Node::Leaf(vec![]));
+                }
+                if let Node::Branch(children) = &mut self.nodes[k] {
+                    children[col] = child as u32;
+                }
+                child
+            };
+        }
+        if let Node::Leaf(cubes) = &mut self.nodes[k] {
+            cubes.push(cube);
+        }
+    }
+
+    /// A stored cube matched with `cube`
+    pub fn find(&self, cube: &PackedCube) -> Option<PackedCube> {
+        self.find_from(0, 0, cube)
+    }
+
+    fn find_from(&self, k: usize, depth: usize, cube: &PackedCube) -> Option<PackedCube> {
+        match &self.nodes[k] {
+            Node::Leaf(cubes) => cubes.iter().find(|d| cube.matched(d)).copied(),
+            Node::Branch(children) => {
+                let col = self.color(cube, depth);
+                let wildcard = Color::Wildcard as usize;
+                let branches: Vec<u32> = if col == wildcard {
+                    children.to_vec()
+                } else {
+                    vec![children[col], children[wildcard]]
+                };
+                branches
+                    .into_iter()
+                    .filter(|&child| child > 0)
+                    .find_map(|child| self.find_from(child as usize, depth + 1, cube))
+            }
+        }
+    }
+
+    fn color(&self, cube: &PackedCube, depth: usize) -> usize {
+        cube.at(self.stickers[depth]) as usize
+    }
+}
+
+/// Visited cubes of one side of the bi-direction search,
+/// with their nodes in the Tree and the symmetries to the canonical cubes (see Reduction).
+/// With wildcards, they are also indexed to find the matched cube.
+pub(crate) struct Visited {
+    map: PackedHashMap<(u32, u8)>,
+    index: Option<Index>,
+}
+
+impl Visited {
+    /// Indexed unless `exact`
+    pub fn new(exact: bool, init_state: &Cube, goals: &[Cube]) -> Self {
+        let mut patterns = vec![init_state];
+        patterns.extend(goals);
+        Self {
+            map: PackedHashMap::default(),
+            index: (!exact).then(|| Index::new(&patterns)),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn contains(&self, cube: &PackedCube) -> bool {
+        self.map.contains_key(cube)
+    }
+
+    pub fn insert(&mut self, cube: PackedCube, node: u32, sym: u8) {
+        if self.map.contains_key(&cube) {
+            return;
+        }
+        if let Some(index) = self.index.as_mut() {
+            index.insert(cube);
+        }
+        self.map.insert(cube, (node, sym));
+    }
+
+    /// Node (and the symmetry) of the cube matched with `cube`
+    pub fn find(&self, cube: &PackedCube) -> Option<(u32, u8)> {
+        match &self.index {
+            None => self.map.get(cube).copied(),
+            Some(index) => index.find(cube).and_then(|d| self.map.get(&d).copied()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TreeNode {
+    parent: u32,
+    op: u16,
+    depth: u16,
+}
+
+const NO_PARENT: u32 = u32::MAX;
+
+/// Search tree with parent pointers, so that a node does not carry its Ops.
+/// Operations are referred by the index in the table of the allowed ops,
+/// which are compiled into the permutations.
+pub(crate) struct Tree {
+    ops: Vec<Operation>,
+    /// Num of the allowed ops at the head of the table
+    num_allowed: usize,
+    perms: Vec<Permutation>,
+    inverses: Vec<Option<usize>>,
+    weights: Vec<usize>,
+    nodes: Vec<TreeNode>,
+    /// Prune U'U' (as UU).
+    /// Off with the mirrors, which do not keep the directions.
+    directed: bool,
+    /// Prune one order of the commuting moves.
+    /// Off with the symmetries, which do not keep the orders, and with the avoided turns,
+    /// which may allow the other order only.
+    ordered: bool,
+}
+
+impl Tree {
+    pub fn new(allowed_ops: &[Operation]) -> Self {
+        let mut tree = Self {
+            ops: vec![],
+            num_allowed: 0,
+            perms: vec![],
+            inverses: vec![],
+            weights: vec![],
+            nodes: vec![],
+            directed: true,
+            ordered: true,
+        };
+        for op in allowed_ops.iter() {
+            tree.op_index(op);
+        }
+        tree.num_allowed = tree.ops.len();
+        tree
+    }
+
+    /// Index of `op` in the table (added if not yet)
+    fn op_index(&mut self, op: &Operation) -> usize {
+        if let Some(k) = self.ops.iter().position(|o| o == op) {
+            return k;
+        }
+        self.ops.push(op.clone());
+        self.perms.push(Permutation::of(op));
+        self.weights.push(Ops::new(vec![op.clone()]).weight());
+        self.inverses = self
+            .ops
+            .iter()
+            .map(|op| {
+                let rev = op.rev();
+                self.ops.iter().position(|o| o == &rev)
+            })
+            .collect();
+        self.ops.len() - 1
+    }
+
+    /// Keep the sequences pruned by the orders (and by the directions with the mirrors),
+    /// as the symmetric cubes of the reduction share one node
+    pub fn reduce_by(&mut self, reduction: &Reduction) {
+        self.directed = !reduction.has_mirror();
+        self.ordered &= reduction.len() <= 1;
+    }
+
+    /// Keep both orders of the commuting moves, to find the one which fits
+    pub fn constrain_by(&mut self, constraints: &Constraints) {
+        self.ordered &= !constraints.restricts_moves();
+    }
+
+    pub fn num_allowed(&self) -> usize {
+        self.num_allowed
+    }
+
+    /// Apply the op `k`
+    pub fn apply(&self, cube: &PackedCube, k: usize) -> PackedCube {
+        cube.permute(&self.perms[k])
+    }
+
+    pub fn weight(&self, k: usize) -> usize {
+        self.weights[k]
+    }
+
+    pub fn root(&mut self) -> u32 {
+        self.nodes.push(TreeNode {
+            parent: NO_PARENT,
+            op: 0,
+            depth: 0,
+        });
+        (self.nodes.len() - 1) as u32
+    }
+
+    pub fn push(&mut self, parent: u32, k: usize) -> u32 {
+        let depth = self.nodes[parent as usize].depth + 1;
+        self.nodes.push(TreeNode {
+            parent,
+            op: k as u16,
+            depth,
+        });
+        (self.nodes.len() - 1) as u32
+    }
+
+    /// Node after `ops` from `parent`
+    pub fn push_ops(&mut self, parent: u32, ops: &Ops) -> u32 {
+        let mut node = parent;
+        for op in ops.data.iter() {
+            let k = self.op_index(op);
+            node = self.push(node, k);
+        }
+        node
+    }
+
+    /// Node after the seeded `ops` (e.g. the prefix) from `parent`, at the depth of `parent`:
+    /// they are not searched, but the moves after them are pruned as usual
+    pub fn seed_ops(&mut self, parent: u32, ops: &Ops) -> u32 {
+        let depth = self.nodes[parent as usize].depth;
+        let node = self.push_ops(parent, ops);
+        self.nodes[node as usize].depth = depth;
+        node
+    }
+
+    /// The op `k` after the path to a node fits the constraints
+    /// (the path from the goal is reversed in the solution)
+    pub fn allows(
+        &self,
+        constraints: &Constraints,
+        path: &Ops,
+        k: usize,
+        from_start: bool,
+    ) -> bool {
+        let mut ops = path.clone();
+        ops.push(self.ops[k].clone());
+        if !from_start {
+            ops = ops.rev();
+        }
+        constraints.allows_part(&ops)
+    }
+
+    pub fn depth(&self, node: u32) -> usize {
+        self.nodes[node as usize].depth as usize
+    }
+
+    fn last(&self, node: u32) -> Option<usize> {
+        let node = self.nodes[node as usize];
+        (node.parent != NO_PARENT).then_some(node.op as usize)
+    }
+
+    /// The op to the node, if the last 2 ops are same
+    fn last_repeat(&self, node: u32) -> Option<usize> {
+        let last = self.last(node)?;
+        let parent = self.nodes[node as usize].parent;
+        (self.last(parent) == Some(last)).then_some(last)
+    }
+
+    /// The op before the last, if the last 2 ops are searched and commute (e.g. U in UD)
+    fn last_commuting(&self, node: u32) -> Option<usize> {
+        let last = self.last(node)?;
+        let parent = self.nodes[node as usize].parent;
+        let before = self.last(parent)?;
+        (self.depth(parent) > 0
+            && last < self.num_allowed
+            && before < self.num_allowed
+            && self.ops[before].commutes(&self.ops[last]))
+        .then_some(before)
+    }
+
+    /// The op `k` after the node is redundant
+    pub fn is_redundant(&self, node: u32, k: usize, from_start: bool) -> bool {
+        let last = self.last(node);
+        // Dont Canceling Move (e.g. UU')
+        if last.is_some() && last == self.inverses[k] {
+            return true;
+        }
+        // Dont repeat Reverse Move (e.g. U'U' is same to UU)
+        if self.directed && last == Some(k) && self.ops[k].is_reversed() == from_start {
+            return true;
+        }
+        // Dont repeat 3 times (e.g. UUU is same to U')
+        if self.last_repeat(node) == Some(k) {
+            return true;
+        }
+        // Dont turn a layer again over a commuting move (e.g. UDU is same to UUD).
+        // This is kept with the symmetries, which keep the layers on an axis.
+        if let Some(before) = self.last_commuting(node) {
+            if self.ops[before].layer() == self.ops[k].layer() {
+                return true;
+            }
+        }
+        // Dont generate both orders of the commuting moves (e.g. DU is same to UD).
+        // The seeded ops (at the depth 0, or not allowed) are kept as they can't be reordered.
+        match last {
+            Some(last)
+                if self.ordered
+                    && last < self.num_allowed
+                    && k < self.num_allowed
+                    && self.depth(node) > 0 =>
+            {
+                self.ops[last].is_unordered(&self.ops[k])
+            }
+            _ => false,
+        }
+    }
+
+    /// Indices of the ops from the root to the node
+    fn path_indices(&self, node: u32) -> Vec<usize> {
+        let mut indices = vec![];
+        let mut node = node;
+        while let Some(k) = self.last(node) {
+            indices.push(k);
+            node = self.nodes[node as usize].parent;
+        }
+        indices.reverse();
+        indices
+    }
+
+    /// Ops from the root to the node
+    pub fn path(&self, node: u32) -> Ops {
+        let data = self.path_indices(node);
+        Ops::new(data.into_iter().map(|k| self.ops[k].clone()).collect())
+    }
+
+    /// Ops through the node from the start and the node from the goal
+    pub fn solution(&self, from_start: u32, from_goal: u32) -> Ops {
+        let mut ops = self.path(from_start);
+        ops.extend(&self.path(from_goal).rev());
+        ops
+    }
+
+    /// Ops through the nodes met by their canonical cubes,
+    /// each given with the symmetry to the canonical cube.
+    /// The ops from the goal are mapped by the symmetries.
+    pub fn solution_reduced(
+        &self,
+        (from_start, s): (u32, u8),
+        (from_goal, t): (u32, u8),
+        reduction: &Reduction,
+    ) -> Ops {
+        if s == t {
+            return self.solution(from_start, from_goal);
+        }
+        let s = reduction.inverse(s);
+        let mut ops_from_goal = Ops::default();
+        for k in self.path_indices(from_goal) {
+            let perm = reduction.conjugate(s, &reduction.conjugate(t, &self.perms[k]));
+            let k = self.perms.iter().position(|p| p == &perm).unwrap();
+            ops_from_goal.push(self.ops[k].clone());
+        }
+        let mut ops = self.path(from_start);
+        ops.extend(&ops_from_goal.rev());
+        ops
+    }
+}
+
+/// Symmetries keeping the goal and the allowed ops.
+/// The symmetric cubes are at the same distance from the goal,
+/// so they share one entry (the canonical cube) in the visited sets.
+pub(crate) struct Reduction {
+    symmetries: Vec<(Symmetry, [Color; 8])>,
+    inverses: Vec<u8>,
+}
+
+impl Reduction {
+    /// No reduction
+    pub fn identity() -> Self {
+        Self {
+            symmetries: vec![],
+            inverses: vec![],
+        }
+    }
+
+    pub fn new(goal: &Cube, allowed_ops: &[Operation]) -> Self {
+        let perms: Vec<Permutation> = allowed_ops.iter().map(Permutation::of).collect();
+        let symmetries: Vec<(Symmetry, [Color; 8])> = goal
+            .symmetries()
+            .into_iter()
+            .filter(|sym| perms.iter().all(|p| perms.contains(&sym.conjugate(p))))
+            .filter_map(|sym| sym.colors(goal).map(|colors| (sym, colors)))
+            .collect();
+        if symmetries.len() <= 1 {
+            return Self::identity();
+        }
+        let inverses = symmetries
+            .iter()
+            .map(|(s, _)| {
+                symmetries
+                    .iter()
+                    .position(|(t, _)| {
+                        s.permutation().then(t.permutation()) == Permutation::identity()
+                    })
+                    .unwrap() as u8
+            })
+            .collect();
+        Self {
+            symmetries,
+            inverses,
+        }
+    }
+
+    /// Reduction if `options.symmetry` and the search is exact (to one goal, without constraints).
+    /// The masked stickers of the goal (`.`) are fine, if the mask is symmetric.
+    pub fn for_search(
+        goals: &[Cube],
+        allowed_ops: &[Operation],
+        exact: bool,
+        options: &SearchOptions,
+    ) -> Self {
+        let reduction =
+            if options.symmetry && exact && goals.len() == 1 && options.constraints.is_empty() {
+                Self::new(&goals[0], allowed_ops)
+            } else {
+                Self::identity()
+            };
+        if options.verbose {
+            info!("Symmetries: {}", reduction.len());
+        }
+        if options.symmetry && reduction.len() <= 1 {
+            warn!(
+                "No symmetry to reduce the search (an exact goal, symmetric with the moves, \
+                 and without constraints is needed)"
+            );
+        }
+        reduction
+    }
+
+    /// Num of the symmetries
+    pub fn len(&self) -> usize {
+        self.symmetries.len().max(1)
+    }
+
+    pub fn has_mirror(&self) -> bool {
+        self.symmetries.iter().any(|(sym, _)| sym.mirror)
+    }
+
+    /// The canonical cube (the smallest in the symmetric ones) and the symmetry to it
+    pub fn canonical(&self, cube: &PackedCube) -> (PackedCube, u8) {
+        let (key, s) = cube.min_recolored(
+            self.symmetries
+                .iter()
+                .map(|(sym, colors)| (sym.permutation(), colors)),
+        );
+        (key, s as u8)
+    }
+
+    fn inverse(&self, s: u8) -> u8 {
+        self.inverses[s as usize]
+    }
+
+    fn conjugate(&self, s: u8, perm: &Permutation) -> Permutation {
+        self.symmetries[s as usize].0.conjugate(perm)
+    }
+}
+
+#[cfg(test)]
+mod test_index {
+    use crate::cube;
+    use crate::entities::*;
+    use crate::read;
+    use crate::solver::index::{Index, Reduction, Tree};
+    use Operation::*;
+
+    #[test]
+    fn test_find() {
+        let solved = cube![
+            Y Y Y;
+            Y Y Y;
+            Y Y Y;
+            R R R G G G O O O B B B;
+            R R R G G G O O O B B B;
+            R R R G G G O O O B B B;
+            W W W;
+            W W W;
+            W W W;
+        ];
+        let goal = cube![
+            Y * Y;
+            Y * Y;
+            Y * Y;
+            R * R G G G O * O B B B;
+            R * R G G G O * O B B B;
+            R * R G G G O * O B B B;
+            W * W;
+            W * W;
+            W * W;
+        ];
+        let mut index = Index::new(&[&goal]);
+        index.insert(PackedCube::pack(&goal));
+        let mut d = goal.clone();
+        for op in [Right(true), Up(true), Front(false)] {
+            d.apply(&op);
+            index.insert(PackedCube::pack(&d));
+        }
+        let find = |c: &Cube| index.find(&PackedCube::pack(c)).map(|d| d.unpack());
+
+        // M moves only the wildcards
+        let mut c = solved.clone();
+        c.apply(&Middle(true));
+        assert_eq!(find(&c), Some(goal.clone()));
+
+        let mut c = solved.clone();
+        c.apply(&Up(true));
+        assert_eq!(find(&c), None);
+
+        let mut c = solved.clone();
+        c.apply(&Right(true));
+        c.apply(&Up(true));
+        let found = find(&c).unwrap();
+        assert!(found.matched(&c));
+        assert_ne!(found, goal);
+    }
+
+    #[test]
+    fn test_tree() {
+        let mut tree = Tree::new(&[Up(true), Up(false), Right(true)]);
+        let root = tree.root();
+        let u = tree.push(root, 0);
+        let uu = tree.push(u, 0);
+        let r = tree.push_ops(uu, &Ops::new(vec![Right(true), X(true)]));
+        assert_eq!(tree.depth(r), 4);
+        assert_eq!(format!("{}", tree.path(r)), "UURx");
+        assert!(tree.is_redundant(u, 1, true));
+        assert!(!tree.is_redundant(u, 0, true));
+        assert!(tree.is_redundant(uu, 0, true));
+        assert!(!tree.is_redundant(uu, 2, true));
+
+        let goal = tree.root();
+        let r_goal = tree.push(goal, 2);
+        assert_eq!(format!("{}", tree.solution(uu, r_goal)), "UUR'");
+
+        let mut tree = Tree::new(&[Up(true), Down(true), Right(true)]);
+        let root = tree.root();
+        let u = tree.push(root, 0);
+        let d = tree.push(root, 1);
+        assert!(!tree.is_redundant(u, 1, true));
+        assert!(tree.is_redundant(d, 0, true));
+        assert!(!tree.is_redundant(d, 2, true));
+        // Seeded at the goal
+        let x = tree.push_ops(root, &Ops::new(vec![X(true)]));
+        assert!(!tree.is_redundant(x, 2, false));
+
+        // With the symmetries, both orders are kept, but not U D U (as U U D)
+        let (_, solved) = read::read("Scramble { R }");
+        let faces = [
+            Up(true),
+            Down(true),
+            Right(true),
+            Left(true),
+            Front(true),
+            Back(true),
+        ];
+        tree.reduce_by(&Reduction::new(&solved, &faces));
+        let du = tree.push(d, 0);
+        assert!(!tree.is_redundant(d, 0, true));
+        assert!(tree.is_redundant(du, 1, true));
+        assert!(!tree.is_redundant(du, 2, true));
+    }
+
+    #[test]
+    fn test_reduction() {
+        let (cube, solved) = read::read("Scramble { R U' F }");
+        let faces = read::parse_ops("U U' D D' F F' B B' L L' R R'").unwrap().1;
+        assert_eq!(Reduction::new(&solved, &faces.data).len(), 48);
+        // <R, U> is kept by the front-back mirror, and by swapping R and U
+        let ru = read::parse_ops("R R' U U'").unwrap().1;
+        assert_eq!(Reduction::new(&solved, &ru.data).len(), 4);
+        assert_eq!(Reduction::new(&cube, &faces.data).len(), 1);
+
+        let reduction = Reduction::new(&solved, &faces.data);
+        let mut u = solved.clone();
+        u.apply(&Operation::Up(true));
+        let mut l = solved.clone();
+        l.apply(&Operation::Left(false));
+        let (key, _) = reduction.canonical(&PackedCube::pack(&u));
+        assert_eq!(key, reduction.canonical(&PackedCube::pack(&l)).0);
+
+        // F2L, with the last layer masked: y rotations and the mirrors
+        let f2l = cube![
+            . . . ;
+            . . . ;
+            . . . ;
+            . . . . . . . . . . . . ;
+            R R R G G G O O O B B B ;
+            R R R G G G O O O B B B ;
+            W W W ;
+            W W W ;
+            W W W ;
+        ];
+        assert_eq!(Reduction::new(&f2l, &faces.data).len(), 8);
+    }
+}
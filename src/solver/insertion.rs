@@ -0,0 +1,242 @@
+use crate::entities::*;
+use crate::solver::commutator::{commutators, Commutator, CommutatorOptions};
+use crate::solver::parallel;
+use std::cmp::Reverse;
+use std::collections::BTreeSet;
+
+#[derive(Debug, Clone)]
+pub struct InsertionOptions {
+    /// Known algorithms to try at each position, also inverted, with the AUFs and rotated
+    pub algorithms: Vec<Ops>,
+    /// Search of the commutators for a leftover 3-cycle (`num` is for each position)
+    pub commutators: CommutatorOptions,
+    /// Max num of the insertions
+    pub num: usize,
+    pub threads: usize,
+}
+
+impl Default for InsertionOptions {
+    fn default() -> Self {
+        Self {
+            algorithms: vec![],
+            commutators: CommutatorOptions {
+                setup_depth: 0,
+                ..CommutatorOptions::default()
+            },
+            num: 10,
+            threads: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Insertion {
+    /// Num of the moves of the skeleton before the insertion
+    pub position: usize,
+    pub algorithm: Ops,
+    /// If the algorithm is a found commutator
+    pub commutator: Option<Commutator>,
+    /// The skeleton with the insertion, cancelled
+    pub solution: Ops,
+    /// Num of the moves cancelled
+    pub cancelled: usize,
+}
+
+/// The stickers `a -> b -> c` of the permutation, if it is a 3-cycle of the corners
+/// or the edges (without twists)
+fn three_cycle(perm: &Permutation) -> Option<(usize, usize, usize)> {
+    let cycles = perm.cycles();
+    if cycles.len() != 1 || cycles[0].pieces.len() != 3 || cycles[0].twist != 0 {
+        return None;
+    }
+    let goes_to = perm.inverse();
+    let a = cycles[0].pieces[0].stickers()[0];
+    let b = goes_to.source(a);
+    let c = goes_to.source(b);
+    Some((a, b, c))
+}
+
+/// Insertions into the skeleton which solve the cube after the scramble, with
+/// the most cancellation first. Each position is tried with the known algorithms,
+/// and with the commutators if the leftover is a 3-cycle.
+pub fn insertions(scramble: &Ops, skeleton: &Ops, options: &InsertionOptions) -> Vec<Insertion> {
+    let skeleton = skeleton.expand();
+    let positions: Vec<usize> = (0..=skeleton.len()).collect();
+    let found = parallel::map(&positions, options.threads, |&position| {
+        let before = Ops::new(skeleton.data[..position].to_vec());
+        let after = Ops::new(skeleton.data[position..].to_vec());
+        // scramble before X after = identity, so X = before' scramble' after'
+        let mut needed = before.rev();
+        needed.extend(&scramble.rev());
+        needed.extend(&after.rev());
+        // The known algorithms or their inverses, with the AUFs and the rotations
+        let variants = EquivalenceOptions {
+            rotation: true,
+            auf: true,
+            last_layer: false,
+        };
+        let moves_only = SimplifyOptions {
+            rotations: true,
+            slices: false,
+        };
+        let mut candidates: Vec<(Ops, Option<Commutator>)> = options
+            .algorithms
+            .iter()
+            .flat_map(|alg| [alg.clone(), alg.rev()])
+            .filter_map(|alg| needed.aligned(&alg, &variants))
+            .map(|alg| (alg.simplify(&moves_only), None))
+            .collect();
+        let needed = Permutation::of_ops(&needed);
+        let three_cycle = three_cycle(&needed).filter(|_| options.commutators.num > 0);
+        if let Some((a, b, c)) = three_cycle {
+            let comms = commutators(a, b, c, &options.commutators).unwrap_or_default();
+            for comm in comms {
+                let alg = comm.algorithm();
+                if Permutation::of_ops(&alg) == needed {
+                    candidates.push((alg, Some(comm)));
+                }
+            }
+        }
+        candidates
+            .into_iter()
+            .map(|(algorithm, commutator)| {
+                let mut solution = before.clone();
+                solution.extend(&algorithm);
+                solution.extend(&after);
+                let solution = solution.shorten();
+                let cancelled = skeleton.len() + algorithm.len() - solution.len();
+                Insertion {
+                    position,
+                    algorithm,
+                    commutator,
+                    solution,
+                    cancelled,
+                }
+            })
+            .collect::<Vec<_>>()
+    });
+    let mut solutions = BTreeSet::new();
+    let mut insertions: Vec<Insertion> = found
+        .into_iter()
+        .flatten()
+        .filter(|insertion| solutions.insert(insertion.solution.clone()))
+        .collect();
+    insertions.sort_by_key(|insertion| {
+        (
+            Reverse(insertion.cancelled),
+            insertion.solution.len(),
+            insertion.position,
+        )
+    });
+    insertions.truncate(options.num);
+    insertions
+}
+
+#[cfg(test)]
+mod test_insertion {
+    use crate::read;
+    use crate::solver::insertion::*;
+    use Operation::*;
+
+    fn ops(s: &str) -> Ops {
+        read::parse_ops(s).unwrap().1
+    }
+
+    #[test]
+    fn test_insertions() {
+        // The skeleton leaves a corner 3-cycle: [R'D'R, U2] is removed from L [R'D'R, U2] F'
+        let scramble = ops("F U2 R' D' R U2 R' D R L'");
+        let skeleton = ops("L F'");
+        assert_eq!(ops("F U2 R' D' R U2 R' D R L' L F'").cycles().len(), 1);
+
+        let options = InsertionOptions {
+            algorithms: vec![ops("R' D' R U2 R' D R U2"), ops("R U R' U'")],
+            commutators: CommutatorOptions {
+                allowed_ops: [Up(true), Down(true), Right(true)]
+                    .iter()
+                    .flat_map(|op| [op.clone(), op.rev()])
+                    .collect(),
+                num: 3,
+                setup_depth: 0,
+                ..CommutatorOptions::default()
+            },
+            ..InsertionOptions::default()
+        };
+        let found = insertions(&scramble, &skeleton, &options);
+        assert!(!found.is_empty());
+        for insertion in found.iter() {
+            assert_eq!(
+                insertion.solution.apply(&scramble.apply(&Cube::solved())),
+                Cube::solved()
+            );
+        }
+        // The known algorithm at the second position
+        let best = &found[0];
+        assert_eq!(best.position, 1);
+        assert_eq!(best.solution, ops("L R' D' R U2 R' D R U2 F'"));
+        assert_eq!(best.cancelled, 0);
+
+        // The same from the inverse or the other side of the known algorithm
+        for alg in ["U2 R' D' R U2 R' D R", "F' D' F U2 F' D F U2"] {
+            let options = InsertionOptions {
+                algorithms: vec![ops(alg)],
+                ..options.clone()
+            };
+            let found = insertions(&scramble, &skeleton, &options);
+            assert_eq!(found[0].solution, best.solution);
+        }
+
+        // The same by the search of the commutators
+        let options = InsertionOptions {
+            algorithms: vec![],
+            ..options
+        };
+        let found = insertions(&scramble, &skeleton, &options);
+        assert_eq!(found[0].solution, best.solution);
+        assert_eq!(
+            found[0].commutator.as_ref().unwrap().to_string(),
+            "[R'D'R, UU]"
+        );
+    }
+
+    #[test]
+    fn test_insertions_empty_skeleton() {
+        // The scramble is a 3-cycle by itself, solved by the inverse
+        let scramble = ops("R' D' R U2 R' D R U2");
+        let options = InsertionOptions {
+            algorithms: vec![scramble.clone()],
+            commutators: CommutatorOptions {
+                num: 0,
+                ..CommutatorOptions::default()
+            },
+            ..InsertionOptions::default()
+        };
+        let found = insertions(&scramble, &Ops::default(), &options);
+        assert_eq!(found[0].position, 0);
+        assert_eq!(
+            found[0].solution.apply(&scramble.apply(&Cube::solved())),
+            Cube::solved()
+        );
+    }
+
+    #[test]
+    fn test_insertions_cancelled() {
+        // L [R'D'R, U2] F' with U2 after the insertion: U2 U2 cancels
+        let scramble = ops("F R' D' R U2 R' D R L'");
+        let skeleton = ops("L U2 F'");
+        let options = InsertionOptions {
+            algorithms: vec![ops("R' D' R U2 R' D R U2"), ops("U2 R' D' R U2 R' D R")],
+            commutators: CommutatorOptions {
+                num: 0,
+                ..CommutatorOptions::default()
+            },
+            ..InsertionOptions::default()
+        };
+        let found = insertions(&scramble, &skeleton, &options);
+        assert!(found.windows(2).all(|w| w[0].cancelled >= w[1].cancelled));
+        let best = &found[0];
+        assert_eq!(best.position, 1);
+        assert_eq!(best.cancelled, 4);
+        assert_eq!(best.solution, ops("L R' D' R U2 R' D R F'"));
+    }
+}
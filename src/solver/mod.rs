@@ -1,6 +1,22 @@
+mod blind;
+mod budget;
 mod cfop;
+mod commutator;
+mod generator;
+mod index;
+mod insertion;
+mod neutral;
+mod parallel;
 mod roux;
 mod search;
-pub use cfop::cfop;
-pub use roux::roux;
-pub use search::{search, search_any, search_one};
+pub use blind::{blind, BlindMethod, BlindSolution, BlindTarget};
+pub use budget::{Budget, Cancel};
+pub use cfop::{cfop, cfop_neutral, cfop_with, CfopOptions, Slot};
+pub use commutator::{commutators, Commutator, CommutatorOptions};
+pub use generator::{generate, GeneratorOptions};
+pub use insertion::{insertions, Insertion, InsertionOptions};
+pub use roux::{roux, roux_neutral, roux_with, RouxOptions};
+pub use search::{
+    aufs, reached_goal, search, search_any, search_any_with, search_goals, search_one, search_with,
+    split_auf, Progress, SearchEvent, SearchOptions, SearchResult, Searcher,
+};
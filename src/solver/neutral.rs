@@ -0,0 +1,128 @@
+use crate::cube;
+use crate::entities::*;
+use crate::solver::parallel;
+use crate::util;
+use log::info;
+
+/// Orientations whose down face has one of `colors`,
+/// relabelled so that the centers are in the standard form.
+pub fn orientations(cube: &Cube, colors: &[Color]) -> Vec<(Ops, Cube)> {
+    let canonical = cube![
+        Y Y Y;
+        Y Y Y;
+        Y Y Y;
+        R R R G G G O O O B B B;
+        R R R G G G O O O B B B;
+        R R R G G G O O O B B B;
+        W W W;
+        W W W;
+        W W W;
+    ];
+    let mut ret = vec![];
+    for rotation in util::rotations() {
+        let c = rotation.apply(cube);
+        if !colors.contains(&c.down[(1, 1)]) {
+            continue;
+        }
+        if let Some(c) = c.recolor(&canonical) {
+            ret.push((rotation, c));
+        }
+    }
+    ret
+}
+
+/// Try `first_step` from every orientation (in parallel with `threads`),
+/// or from one per down color if the step is the same up to y.
+/// Returns the rotations, the oriented cubes and the first steps, the shortest first,
+/// to fall back on when the rest of the solve fails.
+pub fn neutral<F>(
+    cube: &Cube,
+    colors: &[Color],
+    threads: usize,
+    y_symmetric: bool,
+    first_step: F,
+) -> Vec<(Ops, Cube, Ops)>
+where
+    F: Fn(&Cube) -> Option<Ops> + Sync,
+{
+    let mut orientations = orientations(cube, colors);
+    if y_symmetric {
+        let mut downs = vec![];
+        orientations.retain(|(rotation, _)| {
+            let down = rotation.apply(cube).down[(1, 1)];
+            let first = !downs.contains(&down);
+            downs.push(down);
+            first
+        });
+    }
+    let algs = parallel::map(&orientations, threads, |(_, c)| first_step(c));
+    let mut ret = vec![];
+    for ((rotation, c), alg) in orientations.into_iter().zip(algs) {
+        if let Some(alg) = alg {
+            info!("Neutral: {} {} (length={})", rotation, alg, alg.len());
+            ret.push((rotation, c, alg));
+        }
+    }
+    ret.sort_by_key(|(_, _, alg)| alg.len());
+    ret
+}
+
+#[cfg(test)]
+mod test_neutral {
+    use crate::entities::*;
+    use crate::read;
+    use crate::solver::neutral::orientations;
+    use crate::solver::{cfop_neutral, roux_neutral};
+    use Color::*;
+
+    #[test]
+    fn test_orientations() {
+        let (cube, _) = read::read("Scramble { R U }");
+        let all = [White, Yellow, Red, Orange, Blue, Green];
+        assert_eq!(orientations(&cube, &all).len(), 24);
+        for (rotation, c) in orientations(&cube, &[White, Yellow]) {
+            assert_eq!(c.down[(1, 1)], White);
+            assert_eq!(c.front[(1, 1)], Red);
+            assert!(rotation.len() <= 4);
+        }
+        assert_eq!(orientations(&cube, &[White, Yellow]).len(), 8);
+    }
+
+    #[test]
+    fn test_cfop_neutral() {
+        // yellow cross on down is already solved
+        let (cube, _) = read::read("Scramble { z2 }");
+        let alg = cfop_neutral(&cube, &[White, Yellow], false).unwrap();
+        assert_eq!(alg.len(), 0);
+        // white cross must be rotated to down first
+        let alg = cfop_neutral(&cube, &[White], false).unwrap();
+        assert_eq!(format!("{}", alg), "xx");
+    }
+
+    #[test]
+    fn test_cfop_neutral_fallback() {
+        // The shortest cross leaves F2L/FR+BR unsolvable; the next orientation is tried
+        let (cube, goal) =
+            read::read("Scramble { R' F' R2 U2 B2 D2 U2 L2 D2 B U2 F' D2 R' B L U' R2 D2 B' L2 }");
+        let all = [White, Yellow, Red, Orange, Blue, Green];
+        let alg = cfop_neutral(&cube, &all, false).unwrap();
+        let c = alg.apply(&cube);
+        assert!(crate::util::rotations()
+            .iter()
+            .any(|rotation| rotation.apply(&c) == goal));
+        assert!(roux_neutral(&cube, &all, false).is_some());
+    }
+
+    #[test]
+    fn test_roux_neutral() {
+        let (cube, goal) = read::read("Scramble { x' U R' }");
+        let alg = roux_neutral(&cube, &[White, Yellow, Red, Orange, Blue, Green], false).unwrap();
+        let c = alg.apply(&cube);
+        for rotation in crate::util::rotations() {
+            if rotation.apply(&c) == goal {
+                return;
+            }
+        }
+        panic!("Not solved: {}", alg);
+    }
+}
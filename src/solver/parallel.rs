@@ -0,0 +1,473 @@
+use crate::entities::*;
+use crate::solver::budget::Meter;
+use crate::solver::index::{Reduction, Tree, Visited};
+use crate::solver::search::{xyz, SearchOptions, SearchResult, MAX_MAP_SIZE};
+use log::info;
+use std::collections::BTreeSet;
+use std::thread;
+
+/// Apply `f` to each item with `threads` threads, keeping the order
+pub fn map<T, R, F>(items: &[T], threads: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    if threads <= 1 || items.len() <= 1 {
+        return items.iter().map(f).collect();
+    }
+    let f = &f;
+    let chunk_size = items.len().div_ceil(threads);
+    thread::scope(|s| {
+        let handles: Vec<_> = items
+            .chunks(chunk_size)
+            .map(|chunk| s.spawn(move || chunk.iter().map(f).collect::<Vec<R>>()))
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    })
+}
+
+/// Level-synchronous bi-direction search.
+/// The frontier of the smaller side is split across the threads, level by level.
+/// Solutions are ordered by the weight (or the cost if ergonomic).
+pub fn search(
+    init_state: &Cube,
+    goals: &[Cube],
+    allowed_ops: Vec<Operation>,
+    options: &SearchOptions,
+) -> SearchResult {
+    let (inits, goals) = options.ends(init_state, goals);
+    let exact = !init_state.has_wildcard() && goals.iter().all(|goal| !goal.has_wildcard());
+    let meter = options.budget.start();
+    let mut tree = Tree::new(&allowed_ops);
+    let reduction = Reduction::for_search(&goals, &allowed_ops, exact, options);
+    tree.reduce_by(&reduction);
+    tree.constrain_by(&options.constraints);
+
+    let mut from_start = Visited::new(exact, init_state, &goals);
+    let mut from_goal = Visited::new(exact, init_state, &goals);
+    let root = tree.root();
+    let start = tree.seed_ops(root, &options.constraints.prefix);
+    let mut frontier_start = vec![];
+    for init in inits.iter() {
+        let c = PackedCube::pack(init);
+        let (key, sym) = reduction.canonical(&c);
+        if from_start.contains(&key) {
+            continue;
+        }
+        from_start.insert(key, start, sym);
+        frontier_start.push((c, start));
+    }
+    let root = tree.root();
+    let goal_node = tree.seed_ops(root, &options.constraints.suffix.rev());
+    let mut frontier_goal = vec![];
+    for (c, ops) in goals.iter().flat_map(xyz) {
+        let c = PackedCube::pack(&c);
+        let (key, sym) = reduction.canonical(&c);
+        if from_goal.contains(&key) {
+            continue;
+        }
+        let node = tree.push_ops(goal_node, &ops);
+        from_goal.insert(key, node, sym);
+        frontier_goal.push((c, node));
+    }
+    let mut depth_start = 0;
+    let mut depth_goal = 0;
+
+    let mut solutions = vec![];
+    let mut solutionset = BTreeSet::new();
+    for (c, _) in frontier_start.iter() {
+        let (key, sym) = reduction.canonical(c);
+        if let Some(from_goal) = from_goal.find(&key) {
+            let solution = tree.solution_reduced((start, sym), from_goal, &reduction);
+            if !options.constraints.allows(&solution) {
+                continue;
+            }
+            if solutionset.insert(solution.clone()) {
+                solutions.push(solution);
+            }
+        }
+    }
+
+    let mut cutoff = false;
+    while solutions.len() < options.candidates() {
+        let can_start = depth_start < options.max_depth && !frontier_start.is_empty();
+        let can_goal = depth_goal < options.max_depth
+            && !frontier_goal.is_empty()
+            && (exact || from_goal.len() <= MAX_MAP_SIZE);
+        let forward = match (can_start, can_goal) {
+            (false, false) => break,
+            (true, false) => true,
+            (false, true) => false,
+            (true, true) => frontier_start.len() <= frontier_goal.len(),
+        };
+        if options.verbose {
+            info!(
+                "Searching depth: {} + {} ({} threads)",
+                depth_start, depth_goal, options.threads
+            );
+        }
+        let (frontier, visited, other) = if forward {
+            (&frontier_start, &from_start, &from_goal)
+        } else {
+            (&frontier_goal, &from_goal, &from_start)
+        };
+        let chunks: Vec<&[(PackedCube, u32)]> = frontier
+            .chunks(frontier.len().div_ceil(options.threads))
+            .collect();
+        let expanded = map(&chunks, options.threads, |chunk| {
+            expand(
+                chunk,
+                &tree,
+                &reduction,
+                (visited, other),
+                forward,
+                options,
+                &meter,
+            )
+        });
+
+        let visited = if forward {
+            &mut from_start
+        } else {
+            &mut from_goal
+        };
+        let mut next = vec![];
+        for (children, found, stopped) in expanded {
+            cutoff |= stopped;
+            for child in children {
+                if !visited.contains(&child.key) {
+                    let node = tree.push(child.parent, child.op);
+                    visited.insert(child.key, node, child.sym);
+                    next.push((child.cube, node));
+                }
+            }
+            for (child, entry_other) in found {
+                let entry = (tree.push(child.parent, child.op), child.sym);
+                let solution = if forward {
+                    tree.solution_reduced(entry, entry_other, &reduction)
+                } else {
+                    tree.solution_reduced(entry_other, entry, &reduction)
+                };
+                let solution = solution.shorten();
+                if !options.constraints.allows(&solution) {
+                    continue;
+                }
+                if solutionset.insert(solution.clone()) {
+                    solutions.push(solution);
+                }
+            }
+        }
+        if forward {
+            frontier_start = next;
+            depth_start += 1;
+        } else {
+            frontier_goal = next;
+            depth_goal += 1;
+        }
+        if cutoff {
+            info!("Search is cut off ({} nodes)", meter.nodes());
+            break;
+        }
+        if let Some(limit) = options.length_limit(&solutions) {
+            if depth_start + depth_goal >= limit {
+                break;
+            }
+        }
+    }
+
+    solutions.sort_by_key(|ops| ops.weight());
+    options.rank(&mut solutions);
+    for solution in solutions.iter() {
+        info!("Solution: {}", solution);
+    }
+    SearchResult { solutions, cutoff }
+}
+
+/// Cube reached by the op from the parent node
+struct Child {
+    cube: PackedCube,
+    /// Canonical cube and the symmetry to it
+    key: PackedCube,
+    sym: u8,
+    parent: u32,
+    op: usize,
+}
+
+/// Child reaching the other side, and the entry of the other side (node, symmetry)
+type Meeting = (Child, (u32, u8));
+
+/// Expand nodes by one move, with the visited cubes of this side and the other.
+/// Returns the new nodes, the meeting points with the other side (and its entry)
+/// and whether the budget is exhausted.
+fn expand(
+    nodes: &[(PackedCube, u32)],
+    tree: &Tree,
+    reduction: &Reduction,
+    (visited, other): (&Visited, &Visited),
+    forward: bool,
+    options: &SearchOptions,
+    meter: &Meter,
+) -> (Vec<Child>, Vec<Meeting>, bool) {
+    let constraints = &options.constraints;
+    let mut next = vec![];
+    let mut found = vec![];
+    let mut ticks = 0;
+    for &(c, node) in nodes {
+        let path = constraints.restricts_moves().then(|| tree.path(node));
+        for k in 0..tree.num_allowed() {
+            if tree.is_redundant(node, k, forward) {
+                continue;
+            }
+            if let Some(path) = &path {
+                if !tree.allows(constraints, path, k, forward) {
+                    continue;
+                }
+            }
+            if !meter.tick(&mut ticks) {
+                return (next, found, true);
+            }
+            let c = tree.apply(&c, k);
+            let (key, sym) = reduction.canonical(&c);
+            if visited.contains(&key) {
+                continue;
+            }
+            let child = Child {
+                cube: c,
+                key,
+                sym,
+                parent: node,
+                op: k,
+            };
+            if let Some(entry_other) = other.find(&key) {
+                found.push((child, entry_other));
+                continue;
+            }
+            next.push(child);
+        }
+    }
+    (next, found, false)
+}
+
+#[cfg(test)]
+mod test_parallel {
+    use crate::entities::*;
+    use crate::read;
+    use crate::solver::parallel::map;
+    use crate::solver::{reached_goal, search_goals, search_with, split_auf, SearchOptions};
+    use Operation::*;
+
+    #[test]
+    fn test_map() {
+        let items: Vec<usize> = (0..100).collect();
+        let squares = map(&items, 4, |x| x * x);
+        assert_eq!(squares, items.iter().map(|x| x * x).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_search() {
+        let (cube, goal) = read::read("Scramble { R U' F D }");
+        let allowed_ops = vec![
+            Up(true),
+            Up(false),
+            Down(true),
+            Down(false),
+            Front(true),
+            Front(false),
+            Right(true),
+            Right(false),
+        ];
+        let options = SearchOptions {
+            threads: 4,
+            ..SearchOptions::new(3, 1)
+        };
+        let result = search_with(&cube, &goal, allowed_ops, &options);
+        assert_eq!(result.solutions.len(), 1);
+        assert_eq!(result.solutions[0].len(), 4);
+        assert_eq!(result.solutions[0].apply(&cube), goal);
+    }
+
+    #[test]
+    fn test_search_symmetry() {
+        let allowed_ops = read::parse_ops("U U' D D' F F' B B' L L' R R'")
+            .unwrap()
+            .1
+            .data;
+        // The half turns are found, though U'U' and UU are symmetric
+        for (scramble, length) in [("R U' F D B'", 5), ("R2 B2 F2 L' F D' B", 10)] {
+            let (cube, goal) = read::read(&format!("Scramble {{ {} }}", scramble));
+            for threads in [1, 4] {
+                let options = SearchOptions {
+                    threads,
+                    symmetry: true,
+                    ..SearchOptions::new(5, 1)
+                };
+                let result = search_with(&cube, &goal, allowed_ops.clone(), &options);
+                assert_eq!(result.solutions.len(), 1);
+                assert_eq!(result.solutions[0].len(), length);
+                assert_eq!(result.solutions[0].apply(&cube), goal);
+            }
+        }
+    }
+
+    #[test]
+    fn test_search_ergonomics() {
+        let (cube, goal) = read::read("Scramble { M2 U M U2 M' U M2 }");
+        let allowed_ops = read::parse_ops("U U' R R' M M'").unwrap().1.data;
+        for threads in [1, 4] {
+            let options = SearchOptions {
+                threads,
+                ..SearchOptions::new(6, 1)
+            };
+            let shortest = search_with(&cube, &goal, allowed_ops.clone(), &options);
+            assert!(shortest.solutions[0].data.contains(&Middle(true)));
+
+            // M is too expensive
+            let options = SearchOptions {
+                ergonomics: Some(Ergonomics {
+                    middle: 100,
+                    ..Ergonomics::default()
+                }),
+                ..options
+            };
+            let result = search_with(&cube, &goal, allowed_ops.clone(), &options);
+            assert_eq!(result.solutions.len(), 1);
+            assert_eq!(result.solutions[0].apply(&cube), goal);
+            assert!(!result.solutions[0].data.contains(&Middle(true)));
+        }
+
+        // R L' x' is 2 moves longer than M, but cheaper
+        let (cube, goal) = read::read("Scramble { M' }");
+        let allowed_ops = read::parse_ops("M M' R R' L L' x x'").unwrap().1.data;
+        for threads in [1, 4] {
+            let options = SearchOptions {
+                threads,
+                ergonomics: Some(Ergonomics {
+                    middle: 100,
+                    ..Ergonomics::default()
+                }),
+                ..SearchOptions::new(3, 1)
+            };
+            let result = search_with(&cube, &goal, allowed_ops.clone(), &options);
+            assert_eq!(result.solutions[0].len(), 3);
+            assert_eq!(result.solutions[0].apply(&cube), goal);
+            let options = SearchOptions {
+                slack: 1,
+                ..options
+            };
+            let result = search_with(&cube, &goal, allowed_ops.clone(), &options);
+            assert_eq!(result.solutions[0].len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_search_goals() {
+        let (cube, _) = read::read("Scramble { U R U R' U' }");
+        let goals: Vec<Cube> = ["U2", "U"]
+            .iter()
+            .map(|s| read::parse_ops(s).unwrap().1.apply(&Cube::solved()))
+            .collect();
+        let allowed_ops = read::parse_ops("U U' R R'").unwrap().1.data;
+        for threads in [1, 4] {
+            let options = SearchOptions {
+                threads,
+                ..SearchOptions::new(3, 1)
+            };
+            let result = search_goals(&cube, &goals, allowed_ops.clone(), &options);
+            assert_eq!(result.solutions.len(), 1);
+            // U R U' R' reaches the second goal
+            assert_eq!(result.solutions[0].len(), 4);
+            assert_eq!(reached_goal(&cube, &goals, &result.solutions[0]), Some(1));
+        }
+    }
+
+    #[test]
+    fn test_search_auf() {
+        // Solved by Sune (8 quarter turns) between the AUFs
+        let (cube, goal) = read::read("Scramble { U R U2 R' U' R U' R' U2 }");
+        let allowed_ops = read::parse_ops("U U' R R'").unwrap().1.data;
+        for threads in [1, 4] {
+            let options = SearchOptions {
+                threads,
+                pre_auf: true,
+                post_auf: true,
+                ..SearchOptions::new(4, 1)
+            };
+            let result = search_with(&cube, &goal, allowed_ops.clone(), &options);
+            let alg = &result.solutions[0];
+            assert_eq!(alg.len(), 8);
+            let (mut ops, post) = split_auf(&cube, std::slice::from_ref(&goal), alg).unwrap();
+            ops.extend(alg);
+            ops.extend(&post);
+            assert_eq!(ops.apply(&cube), goal);
+        }
+    }
+
+    #[test]
+    fn test_search_constraints() {
+        let (cube, goal) = read::read("Scramble { M2 U' M U2 M' U' M2 }");
+        let allowed_ops = read::parse_ops("U U' R R' M M'").unwrap().1.data;
+        let ops = |s| read::parse_ops(s).unwrap().1;
+        for threads in [1, 4] {
+            let options = SearchOptions {
+                threads,
+                constraints: Constraints {
+                    prefix: ops("R U'"),
+                    limits: vec![(ops("M"), 0)],
+                    ..Constraints::default()
+                },
+                ..SearchOptions::new(6, 1)
+            };
+            let result = search_with(&cube, &goal, allowed_ops.clone(), &options);
+            assert_eq!(result.solutions.len(), 1);
+            let alg = &result.solutions[0];
+            assert_eq!(alg.apply(&cube), goal);
+            assert!(options.constraints.allows(alg));
+        }
+    }
+
+    #[test]
+    fn test_search_prefix_not_undone() {
+        let (cube, goal) = read::read("Scramble { M2 U' M U2 M' U' M2 }");
+        let allowed_ops = read::parse_ops("U U' R R' M M'").unwrap().1.data;
+        for threads in [1, 4] {
+            let options = SearchOptions {
+                threads,
+                constraints: Constraints {
+                    prefix: read::parse_ops("R U'").unwrap().1,
+                    ..Constraints::default()
+                },
+                ..SearchOptions::new(6, 3)
+            };
+            let result = search_with(&cube, &goal, allowed_ops.clone(), &options);
+            assert!(!result.solutions.is_empty());
+            for alg in result.solutions.iter() {
+                assert_eq!(alg.apply(&cube), goal);
+                // The search goes on from the prefix, not cancelling it (e.g. R U' U R')
+                assert_ne!(alg.data[2], Up(true));
+            }
+        }
+    }
+
+    #[test]
+    fn test_search_avoid_other_order() {
+        // F B is avoided, but B F commutes and fits
+        let (cube, goal) = read::read("Scramble { B' F' }");
+        let allowed_ops = read::parse_ops("F F' B B' U U'").unwrap().1.data;
+        for threads in [1, 4] {
+            let options = SearchOptions {
+                threads,
+                constraints: Constraints {
+                    avoid: vec![read::parse_ops("F B").unwrap().1],
+                    ..Constraints::default()
+                },
+                ..SearchOptions::new(4, 1)
+            };
+            let result = search_with(&cube, &goal, allowed_ops.clone(), &options);
+            assert_eq!(result.solutions, vec![read::parse_ops("B F").unwrap().1]);
+        }
+    }
+}
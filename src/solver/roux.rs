@@ -1,10 +1,51 @@
 use crate::cube;
 use crate::entities::*;
 use crate::read;
-use crate::solver::{search_any, search_one};
+use crate::solver::neutral::neutral;
+use crate::solver::{search_any_with, search_one, SearchOptions};
 use log::info;
 
+#[derive(Debug, Clone, Default)]
+pub struct RouxOptions {
+    /// Color neutral: colors to try on the down face
+    pub neutral: Option<Vec<Color>>,
+    /// Num of threads for the orientations and the CMLL ways
+    pub threads: usize,
+}
+
 pub fn roux(cube: &Cube, verbose: bool) -> Option<Ops> {
+    roux_with(cube, &RouxOptions::default(), verbose)
+}
+
+/// Roux from the orientation with the shortest first block (or the next ones if the rest fails),
+/// whose down face has one of `colors`
+pub fn roux_neutral(cube: &Cube, colors: &[Color], verbose: bool) -> Option<Ops> {
+    let options = RouxOptions {
+        neutral: Some(colors.to_vec()),
+        ..RouxOptions::default()
+    };
+    roux_with(cube, &options, verbose)
+}
+
+pub fn roux_with(cube: &Cube, options: &RouxOptions, verbose: bool) -> Option<Ops> {
+    match &options.neutral {
+        Some(colors) => neutral(cube, colors, options.threads, false, |c| {
+            first_block(c, verbose)
+        })
+        .into_iter()
+        .find_map(|(rotation, cube, alg)| {
+            let mut algorithm = rotation;
+            algorithm.extend(&complete(&cube, alg, options.threads, verbose)?);
+            Some(algorithm)
+        }),
+        None => {
+            let alg = first_block(cube, verbose)?;
+            complete(cube, alg, options.threads, verbose)
+        }
+    }
+}
+
+fn first_block(cube: &Cube, verbose: bool) -> Option<Ops> {
     let mut cube: Cube = cube.clone();
     let mut algorithm = Ops::default();
 
@@ -75,12 +116,20 @@ pub fn roux(cube: &Cube, verbose: bool) -> Option<Ops> {
     match search_one(&subcube, &subgoal, allowed_ops, 8, verbose) {
         Some(alg) => {
             algorithm.extend(&alg);
-            cube = alg.apply(&cube);
         }
         None => {
             return None;
         }
     }
+    Some(algorithm)
+}
+
+/// Solve the rest after the first block `alg`
+fn complete(cube: &Cube, alg: Ops, threads: usize, verbose: bool) -> Option<Ops> {
+    let mut cube = alg.apply(cube);
+    let mut algorithm = alg;
+
+    use Operation::*;
 
     info!("SB/1");
     let subgoal = cube![
@@ -167,7 +216,7 @@ pub fn roux(cube: &Cube, verbose: bool) -> Option<Ops> {
     let sexy = Operation::Compound(
         "Sx".to_string(),
         true,
-        read::parse_ops(&"RUR'U'").unwrap().1.data,
+        read::parse_ops("RUR'U'").unwrap().1.data,
     );
     let ways = vec![
         (vec![Up(true), Up(false), Right(true), Right(false)], 8),
@@ -207,7 +256,15 @@ pub fn roux(cube: &Cube, verbose: bool) -> Option<Ops> {
             8,
         ),
     ];
-    match search_any(&subcube, &subgoal, ways, verbose, 20) {
+    // The AUFs before and after are free in the search
+    let options = SearchOptions {
+        threads,
+        verbose,
+        pre_auf: true,
+        post_auf: true,
+        ..SearchOptions::default()
+    };
+    match search_any_with(&subcube, &subgoal, ways, 20, &options) {
         Some(alg) => {
             algorithm.extend(&alg);
             cube = alg.apply(&cube);
@@ -241,5 +298,9 @@ pub fn roux(cube: &Cube, verbose: bool) -> Option<Ops> {
     }
 
     info!("{}", cube);
-    Some(algorithm.shorten())
+    let options = SimplifyOptions {
+        slices: true,
+        ..SimplifyOptions::default()
+    };
+    Some(algorithm.simplify(&options))
 }
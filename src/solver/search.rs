@@ -1,10 +1,130 @@
 use crate::entities::*;
+use crate::solver::budget::{Budget, Meter};
+use crate::solver::index::{Reduction, Tree, Visited};
+use crate::solver::parallel;
 use crate::trace;
 use log::info;
 use std::cmp::Reverse;
 use std::collections::*;
 use Operation::*;
 
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchResult {
+    pub solutions: Vec<Ops>,
+    /// The search was stopped by the budget (or cancelled) before completion
+    pub cutoff: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    pub max_depth: usize,
+    /// Max num of solutions. The commuting moves are searched in one order
+    /// (e.g. U D but not D U), so such variants are not counted.
+    pub num: usize,
+    pub budget: Budget,
+    /// Num of worker threads (1 is single-threaded)
+    pub threads: usize,
+    /// Share one entry among the symmetric cubes
+    /// (if the goal and the allowed ops are symmetric; masked stickers are fine, wildcards not).
+    /// Less solutions may be found, as the symmetric ones are skipped.
+    pub symmetry: bool,
+    /// Rank the solutions by the cost of the finger tricks instead of the length.
+    /// All the solutions up to `slack` moves longer than the shortest are searched for,
+    /// and the cheapest are kept.
+    pub ergonomics: Option<Ergonomics>,
+    /// Extra length of the solutions to rank with `ergonomics`
+    pub slack: usize,
+    /// Any AUF may be done before the solution (see `split_auf`)
+    pub pre_auf: bool,
+    /// The goals are solved modulo AUF: any AUF may be done after the solution
+    pub post_auf: bool,
+    /// The moves which do not fit are not searched (the solutions which do not fit
+    /// are skipped, and not counted in `num`). The prefix and the suffix are inside of the AUFs,
+    /// and not counted in `max_depth`.
+    pub constraints: Constraints,
+    pub verbose: bool,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: 9,
+            num: 1,
+            budget: Budget::default(),
+            threads: 1,
+            symmetry: false,
+            ergonomics: None,
+            slack: 2,
+            pre_auf: false,
+            post_auf: false,
+            constraints: Constraints::default(),
+            verbose: false,
+        }
+    }
+}
+
+impl SearchOptions {
+    pub fn new(max_depth: usize, num: usize) -> Self {
+        Self {
+            max_depth,
+            num,
+            ..Self::default()
+        }
+    }
+
+    /// Num of the solutions to search for (all of them with `ergonomics`, up to `length_limit`)
+    pub(crate) fn candidates(&self) -> usize {
+        if self.ergonomics.is_some() {
+            usize::MAX
+        } else {
+            self.num
+        }
+    }
+
+    /// Max length of the solutions to rank with `ergonomics`, after the shortest is found
+    pub(crate) fn length_limit(&self, solutions: &[Ops]) -> Option<usize> {
+        self.ergonomics.as_ref()?;
+        let shortest = solutions.iter().map(|ops| ops.weight()).min()?;
+        Some(shortest + self.slack)
+    }
+
+    /// The init states after each pre-AUF and the prefix, and the goals before the suffix
+    /// and each post-AUF
+    pub(crate) fn ends(&self, init_state: &Cube, goals: &[Cube]) -> (Vec<Cube>, Vec<Cube>) {
+        let with_aufs = |cubes: &[Cube], auf: bool, ops: &Ops| -> Vec<Cube> {
+            let aufs = if auf {
+                aufs().to_vec()
+            } else {
+                vec![Ops::default()]
+            };
+            cubes
+                .iter()
+                .flat_map(|c| aufs.iter().map(|auf| ops.apply(&auf.apply(c))))
+                .collect()
+        };
+        let constraints = &self.constraints;
+        (
+            with_aufs(
+                std::slice::from_ref(init_state),
+                self.pre_auf,
+                &constraints.prefix,
+            ),
+            with_aufs(goals, self.post_auf, &constraints.suffix.rev()),
+        )
+    }
+
+    /// Sort the solutions by the cost (if ergonomic, up to `length_limit`) and keep `num` of them
+    pub(crate) fn rank(&self, solutions: &mut Vec<Ops>) {
+        if let Some(limit) = self.length_limit(solutions) {
+            solutions.retain(|ops| ops.weight() <= limit);
+        }
+        if let Some(ergonomics) = &self.ergonomics {
+            solutions.sort_by_key(|ops| ops.cost(ergonomics));
+        }
+        solutions.truncate(self.num);
+    }
+}
+
 pub fn search(
     init_state: &Cube,
     goal: &Cube,
@@ -13,24 +133,72 @@ pub fn search(
     num: usize,
     verbose: bool,
 ) -> Vec<Ops> {
-    let xyz_map = xyz(goal);
-    let exact = !init_state.has_wildcard() && !goal.has_wildcard();
-    if verbose {
-        trace!(exact);
-    }
-    solve(
-        init_state,
-        &xyz_map,
-        allowed_ops,
-        max_depth,
-        num,
-        exact,
+    let options = SearchOptions {
         verbose,
-    )
+        ..SearchOptions::new(max_depth, num)
+    };
+    search_with(init_state, goal, allowed_ops, &options).solutions
+}
+
+/// Search within the budget, in parallel if `options.threads > 1`.
+/// The solutions found so far are returned even if it is cut off.
+pub fn search_with(
+    init_state: &Cube,
+    goal: &Cube,
+    allowed_ops: Vec<Operation>,
+    options: &SearchOptions,
+) -> SearchResult {
+    search_goals(init_state, std::slice::from_ref(goal), allowed_ops, options)
+}
+
+/// Search to any of the goals, as `search_with`
+pub fn search_goals(
+    init_state: &Cube,
+    goals: &[Cube],
+    allowed_ops: Vec<Operation>,
+    options: &SearchOptions,
+) -> SearchResult {
+    if options.threads > 1 {
+        parallel::search(init_state, goals, allowed_ops, options)
+    } else {
+        Searcher::with_goals(init_state, goals, allowed_ops, options).run(|_| {})
+    }
+}
+
+/// Index of the first goal reached by the solution
+pub fn reached_goal(init_state: &Cube, goals: &[Cube], solution: &Ops) -> Option<usize> {
+    let c = solution.apply(init_state);
+    goals.iter().position(|goal| c.matched(goal))
+}
+
+/// No AUF, U, U' and U2
+pub fn aufs() -> [Ops; 4] {
+    [
+        Ops::default(),
+        Ops::new(vec![Up(true)]),
+        Ops::new(vec![Up(false)]),
+        Ops::new(vec![Up(true), Up(true)]),
+    ]
+}
+
+/// The AUFs before and after the solution found with `pre_auf` or `post_auf`,
+/// to reach one of the goals
+pub fn split_auf(init_state: &Cube, goals: &[Cube], solution: &Ops) -> Option<(Ops, Ops)> {
+    for pre in aufs() {
+        for post in aufs() {
+            let mut ops = pre.clone();
+            ops.extend(solution);
+            ops.extend(&post);
+            if reached_goal(init_state, goals, &ops).is_some() {
+                return Some((pre, post));
+            }
+        }
+    }
+    None
 }
 
 /// Set of all cube states from the given state only using xyz
-fn xyz(state: &Cube) -> BTreeMap<Cube, Ops> {
+pub(crate) fn xyz(state: &Cube) -> BTreeMap<Cube, Ops> {
     const MAX_DEPTH: usize = 3;
     const ALLOWED_OPS: [Operation; 6] = [X(true), X(false), Y(true), Y(false), Z(true), Z(false)];
     let mut map = BTreeMap::new();
@@ -54,153 +222,295 @@ fn xyz(state: &Cube) -> BTreeMap<Cube, Ops> {
                 q.push_back((c, ops));
             }
         }
-        if !map.contains_key(&c) {
-            map.insert(c, ops);
-        }
+        map.entry(c).or_insert(ops);
     }
     map
 }
 
-/// Bi-direction search with xyz_map
-fn solve(
-    init_state: &Cube,
-    xyz_map: &BTreeMap<Cube, Ops>,
-    allowed_ops: Vec<Operation>,
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Progress {
+    /// Length of the operations now searching
+    pub depth: usize,
+    /// Num of visited nodes
+    pub nodes: usize,
+    /// Num of cubes reached from the init state
+    pub from_start: usize,
+    /// Num of cubes reached from the goal
+    pub from_goal: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SearchEvent {
+    Solution(Ops),
+    Progress(Progress),
+}
+
+/// Bi-direction search with xyz_map, which can be run step by step.
+/// As an Iterator, this yields solutions and progress when they are found.
+pub struct Searcher {
+    init_state: Cube,
+    tree: Tree,
     max_depth: usize,
     num: usize,
+    /// To rank the solutions
+    options: SearchOptions,
     exact: bool,
     verbose: bool,
-) -> Vec<Ops> {
-    let mut cubes_from_start = BTreeMap::new();
-    let mut cubes_from_goal = BTreeMap::new();
-    const MAX_MAP_SIZE: usize = 20_000;
-
-    let mut q = BinaryHeap::new();
-    q.push((Reverse((0, true)), init_state.clone(), Ops::default(), true));
-    for (c, ops) in xyz_map.iter() {
-        q.push((
-            Reverse((ops.weight(), false)),
-            c.clone(),
-            ops.clone(),
-            false,
-        ));
-    }
-
-    let mut solutions = vec![];
-    let mut solutionset = BTreeSet::new();
-    let add_solution =
-        |solutions: &mut Vec<Ops>, solutionset: &mut BTreeSet<Ops>, solution: Ops| {
-            if solutionset.contains(&solution) {
-                return;
-            }
-            info!("Solution: {}", solution);
-            if verbose {
-                let c = solution.apply(&init_state);
-                info!("Validation:\n{}", c);
-            }
-            solutions.push(solution.clone());
-            solutionset.insert(solution);
-        };
+    meter: Meter,
+    ticks: usize,
+    q: BinaryHeap<Node>,
+    reduction: Reduction,
+    cubes_from_start: Visited,
+    cubes_from_goal: Visited,
+    solutions: Vec<Ops>,
+    solutionset: BTreeSet<Ops>,
+    searching_depth: usize,
+    events: VecDeque<SearchEvent>,
+    cutoff: bool,
+    done: bool,
+}
 
-    let found = |cube: &Cube, ops: &Ops, cubes_from_goal: &BTreeMap<Cube, Ops>| -> Option<Ops> {
-        if exact {
-            if let Some(ops_from_goal) = cubes_from_goal.get(&cube) {
-                let mut ops = ops.clone();
-                ops.extend(&ops_from_goal.rev());
-                return Some(ops.clone());
-            }
-        } else {
-            for (d, ops_from_goal) in cubes_from_goal.iter() {
-                if cube.matched(d) {
-                    let mut ops = ops.clone();
-                    ops.extend(&ops_from_goal.rev());
-                    return Some(ops.clone());
-                }
+/// (priority, cube, node in the tree).
+/// The same priorities are popped by the order of `Cube` (as `PackedCube` keeps it).
+type Node = (Reverse<(usize, bool)>, PackedCube, u32);
+
+/// Max num of the cubes from the goal with wildcards.
+/// The old cap is kept on wasm, whose memory is limited in the browser.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) const MAX_MAP_SIZE: usize = 1_000_000;
+#[cfg(target_arch = "wasm32")]
+pub(crate) const MAX_MAP_SIZE: usize = 20_000;
+/// Report progress every this num of nodes
+const PROGRESS_INTERVAL: usize = 10_000;
+
+impl Searcher {
+    pub fn new(
+        init_state: &Cube,
+        goal: &Cube,
+        allowed_ops: Vec<Operation>,
+        options: &SearchOptions,
+    ) -> Self {
+        Self::with_goals(init_state, std::slice::from_ref(goal), allowed_ops, options)
+    }
+
+    /// Search to any of the goals (see `reached_goal` for which one)
+    pub fn with_goals(
+        init_state: &Cube,
+        goals: &[Cube],
+        allowed_ops: Vec<Operation>,
+        options: &SearchOptions,
+    ) -> Self {
+        let (inits, goals) = options.ends(init_state, goals);
+        let goals = &goals[..];
+        let exact = !init_state.has_wildcard() && goals.iter().all(|goal| !goal.has_wildcard());
+        if options.verbose {
+            trace!(exact);
+        }
+        let reduction = Reduction::for_search(goals, &allowed_ops, exact, options);
+        let mut tree = Tree::new(&allowed_ops);
+        tree.reduce_by(&reduction);
+        tree.constrain_by(&options.constraints);
+        tree.constrain_by(&options.constraints);
+        let mut q = BinaryHeap::new();
+        let root = tree.root();
+        let start = tree.seed_ops(root, &options.constraints.prefix);
+        for init in inits.iter() {
+            q.push((Reverse((0, true)), PackedCube::pack(init), start));
+        }
+        let root = tree.root();
+        let goal_node = tree.seed_ops(root, &options.constraints.suffix.rev());
+        for goal in goals.iter() {
+            for (c, ops) in xyz(goal).into_iter() {
+                let node = tree.push_ops(goal_node, &ops);
+                q.push((Reverse((ops.weight(), false)), PackedCube::pack(&c), node));
             }
         }
-        None
-    };
+        Self {
+            init_state: init_state.clone(),
+            tree,
+            max_depth: options.max_depth,
+            num: options.candidates(),
+            options: options.clone(),
+            exact,
+            verbose: options.verbose,
+            meter: options.budget.start(),
+            ticks: 0,
+            q,
+            reduction,
+            cubes_from_start: Visited::new(exact, init_state, goals),
+            cubes_from_goal: Visited::new(exact, init_state, goals),
+            solutions: vec![],
+            solutionset: BTreeSet::new(),
+            searching_depth: 0,
+            events: VecDeque::new(),
+            cutoff: false,
+            done: false,
+        }
+    }
 
-    let found_reverse =
-        |cube: &Cube, ops_from_goal: &Ops, cubes_from_start: &BTreeMap<Cube, Ops>| -> Option<Ops> {
-            if exact {
-                if let Some(ops) = cubes_from_start.get(&cube) {
-                    let mut ops = ops.clone();
-                    ops.extend(&ops_from_goal.rev());
-                    return Some(ops.clone());
-                }
-            } else {
-                for (d, ops) in cubes_from_start.iter() {
-                    if cube.matched(d) {
-                        let mut ops = ops.clone();
-                        ops.extend(&ops_from_goal.rev());
-                        return Some(ops.clone());
-                    }
-                }
-            }
-            None
-        };
+    /// Run to the end, calling `f` for each event
+    pub fn run<F: FnMut(&SearchEvent)>(mut self, mut f: F) -> SearchResult {
+        for event in self.by_ref() {
+            f(&event);
+        }
+        self.result()
+    }
+
+    /// Visit at most `nodes` nodes (or to the end), and return the events on the way
+    pub fn visit(&mut self, nodes: usize) -> Vec<SearchEvent> {
+        let target = self.meter.nodes() + nodes;
+        while !self.done && self.meter.nodes() < target {
+            self.step();
+        }
+        self.events.drain(..).collect()
+    }
+
+    pub fn result(&self) -> SearchResult {
+        let mut solutions = self.solutions.clone();
+        self.options.rank(&mut solutions);
+        SearchResult {
+            solutions,
+            cutoff: self.cutoff,
+        }
+    }
 
-    let mut searching_depth: usize = 0;
-    while let Some((_, c, ops, from_start)) = q.pop() {
-        if solutions.len() >= num {
-            break;
+    pub fn is_done(&self) -> bool {
+        self.done && self.events.is_empty()
+    }
+
+    pub fn progress(&self) -> Progress {
+        Progress {
+            depth: self.searching_depth,
+            nodes: self.meter.nodes(),
+            from_start: self.cubes_from_start.len(),
+            from_goal: self.cubes_from_goal.len(),
+        }
+    }
+
+    fn add_solution(&mut self, solution: Ops) {
+        if !self.options.constraints.allows(&solution) {
+            return;
+        }
+        if self.solutionset.contains(&solution) {
+            return;
+        }
+        info!("Solution: {}", solution);
+        if self.verbose {
+            let c = solution.apply(&self.init_state);
+            info!("Validation:\n{}", c);
+        }
+        self.solutions.push(solution.clone());
+        self.solutionset.insert(solution.clone());
+        self.events.push_back(SearchEvent::Solution(solution));
+    }
+
+    fn found(&self, cube: &PackedCube, from_start: (u32, u8)) -> Option<Ops> {
+        let from_goal = self.cubes_from_goal.find(cube)?;
+        Some(
+            self.tree
+                .solution_reduced(from_start, from_goal, &self.reduction),
+        )
+    }
+
+    fn found_reverse(&self, cube: &PackedCube, from_goal: (u32, u8)) -> Option<Ops> {
+        let from_start = self.cubes_from_start.find(cube)?;
+        Some(
+            self.tree
+                .solution_reduced(from_start, from_goal, &self.reduction),
+        )
+    }
+
+    /// Visit one node
+    fn step(&mut self) {
+        if self.solutions.len() >= self.num {
+            self.done = true;
+            return;
+        }
+        let Some((Reverse((weight, from_start)), c, node)) = self.q.pop() else {
+            self.done = true;
+            return;
+        };
+        // The solutions up to the limit meet at the nodes up to the half of it
+        if let Some(limit) = self.options.length_limit(&self.solutions) {
+            if weight > limit.div_ceil(2) {
+                self.done = true;
+                return;
+            }
+        }
+        if !self.meter.tick(&mut self.ticks) {
+            info!("Search is cut off ({} nodes)", self.meter.nodes());
+            self.cutoff = true;
+            self.done = true;
+            return;
         }
-        if verbose && ops.len() > searching_depth {
-            searching_depth = ops.len();
-            info!("Searching depth: {}", searching_depth);
+        let depth = self.tree.depth(node);
+        if depth > self.searching_depth {
+            self.searching_depth = depth;
+            if self.verbose {
+                info!("Searching depth: {}", self.searching_depth);
+            }
+            self.events
+                .push_back(SearchEvent::Progress(self.progress()));
+        } else if self.meter.nodes().is_multiple_of(PROGRESS_INTERVAL) {
+            self.events
+                .push_back(SearchEvent::Progress(self.progress()));
         }
+        let (key, sym) = self.reduction.canonical(&c);
         if from_start {
-            if !exact && cubes_from_goal.len() > MAX_MAP_SIZE {
-                continue;
+            if !self.exact && self.cubes_from_goal.len() > MAX_MAP_SIZE {
+                return;
             }
-            if cubes_from_start.contains_key(&c) {
-                continue;
+            if self.cubes_from_start.contains(&key) {
+                return;
             }
-            cubes_from_start.insert(c.clone(), ops.clone());
-            if let Some(solution) = found(&c, &ops, &cubes_from_goal) {
-                add_solution(&mut solutions, &mut solutionset, solution.shorten());
-                continue;
+            self.cubes_from_start.insert(key, node, sym);
+            if let Some(solution) = self.found(&key, (node, sym)) {
+                self.add_solution(solution.shorten());
+                return;
             }
         } else {
-            if !exact && cubes_from_goal.len() > MAX_MAP_SIZE {
-                continue;
+            if !self.exact && self.cubes_from_goal.len() > MAX_MAP_SIZE {
+                return;
             }
-            if cubes_from_goal.contains_key(&c) {
-                continue;
+            if self.cubes_from_goal.contains(&key) {
+                return;
             }
-            cubes_from_goal.insert(c.clone(), ops.clone());
-            if let Some(solution) = found_reverse(&c, &ops, &cubes_from_start) {
-                add_solution(&mut solutions, &mut solutionset, solution.shorten());
-                continue;
+            self.cubes_from_goal.insert(key, node, sym);
+            if let Some(solution) = self.found_reverse(&key, (node, sym)) {
+                self.add_solution(solution.shorten());
+                return;
             }
         }
-        if ops.len() >= max_depth {
-            continue;
+        if depth >= self.max_depth {
+            return;
         }
-        let last = ops.last();
-        let last_repeat = ops.last_repeat();
-        for op in allowed_ops.iter() {
-            // Dont Canceling Move (e.g. UU')
-            if last == Some(op.rev()) {
-                continue;
-            }
-            // Dont repeat Reverse Move (e.g. U'U' is same to UU)
-            if last == Some(op.clone()) && op.is_reversed() == from_start {
+        let constraints = &self.options.constraints;
+        let path = constraints.restricts_moves().then(|| self.tree.path(node));
+        for k in 0..self.tree.num_allowed() {
+            if self.tree.is_redundant(node, k, from_start) {
                 continue;
             }
-            // Dont repeat 3 times (e.g. UUU is same to U')
-            if last_repeat == Some(op.clone()) {
-                continue;
+            if let Some(path) = &path {
+                if !self.tree.allows(constraints, path, k, from_start) {
+                    continue;
+                }
             }
-            let mut c = c.clone();
-            c.apply(op);
-            let mut ops = ops.clone();
-            ops.push(op.clone());
-            q.push((Reverse((ops.weight(), from_start)), c, ops, from_start));
+            let c = self.tree.apply(&c, k);
+            let child = self.tree.push(node, k);
+            let weight = weight + self.tree.weight(k);
+            self.q.push((Reverse((weight, from_start)), c, child));
         }
     }
-    solutions
+}
+
+impl Iterator for Searcher {
+    type Item = SearchEvent;
+    fn next(&mut self) -> Option<SearchEvent> {
+        while self.events.is_empty() && !self.done {
+            self.step();
+        }
+        self.events.pop_front()
+    }
 }
 
 pub fn search_one(
@@ -211,26 +521,64 @@ pub fn search_one(
     verbose: bool,
 ) -> Option<Ops> {
     let algs = search(init_state, goal, allowed_ops, max_depth, 1, verbose);
-    algs.get(0).cloned()
+    algs.first().cloned()
 }
 
+/// Search with each way (allowed ops and max depth), in parallel with `threads`.
+/// Returns the first algorithm not longer than `better_length`, or the shortest.
 pub fn search_any(
     init_state: &Cube,
     goal: &Cube,
     ways: Vec<(Vec<Operation>, usize)>,
     verbose: bool,
     better_length: usize,
+    threads: usize,
 ) -> Option<Ops> {
-    let mut min_length = 999;
+    let options = SearchOptions {
+        threads,
+        verbose,
+        ..SearchOptions::default()
+    };
+    search_any_with(init_state, goal, ways, better_length, &options)
+}
+
+/// `search_any` with the options (`max_depth` is of each way, and each way is single-threaded).
+/// If ergonomic, the one with the least cost is returned instead.
+/// The AUFs by `pre_auf` and `post_auf` are included in the algorithm.
+pub fn search_any_with(
+    init_state: &Cube,
+    goal: &Cube,
+    ways: Vec<(Vec<Operation>, usize)>,
+    better_length: usize,
+    options: &SearchOptions,
+) -> Option<Ops> {
+    let score = |alg: &Ops| match &options.ergonomics {
+        Some(ergonomics) => alg.cost(ergonomics),
+        None => alg.len(),
+    };
+    let mut min_score = usize::MAX;
     let mut ret = None;
-    for (allowed_ops, max_depth) in ways {
-        let algs = search(init_state, goal, allowed_ops, max_depth, 1, verbose);
-        if let Some(alg) = algs.get(0).map(|alg| alg.expand().shorten()) {
-            if alg.len() <= better_length {
+    for batch in ways.chunks(options.threads.max(1)) {
+        let algs = parallel::map(batch, options.threads, |(allowed_ops, max_depth)| {
+            let options = SearchOptions {
+                max_depth: *max_depth,
+                num: 1,
+                threads: 1,
+                ..options.clone()
+            };
+            let result = search_with(init_state, goal, allowed_ops.clone(), &options);
+            let alg = result.solutions.first()?;
+            let (mut ops, post) = split_auf(init_state, std::slice::from_ref(goal), alg)?;
+            ops.extend(alg);
+            ops.extend(&post);
+            Some(ops.expand().shorten())
+        });
+        for alg in algs.into_iter().flatten() {
+            if options.ergonomics.is_none() && alg.len() <= better_length {
                 return Some(alg);
             }
-            if alg.len() < min_length {
-                min_length = alg.len();
+            if score(&alg) < min_score {
+                min_score = score(&alg);
                 ret = Some(alg);
             }
         }
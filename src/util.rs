@@ -137,3 +137,28 @@ pub fn centers() -> Vec<Center> {
         (Right, 1, 1),
     ]
 }
+
+use crate::entities::{Operation, Ops};
+/// All 24 orientations, as rotations from the standard form
+pub fn rotations() -> Vec<Ops> {
+    use Operation::*;
+    let mut rotations = vec![];
+    for pre in [
+        vec![],
+        vec![X(true)],
+        vec![X(false)],
+        vec![X(true), X(true)],
+        vec![Z(true)],
+        vec![Z(false)],
+    ] {
+        for post in [
+            vec![],
+            vec![Y(true)],
+            vec![Y(true), Y(true)],
+            vec![Y(false)],
+        ] {
+            rotations.push(Ops::new([pre.clone(), post].concat()));
+        }
+    }
+    rotations
+}